@@ -1,9 +1,49 @@
+use std::fmt;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
 
-use crate::types::{AlbumId, ArtistId, SongId};
+use crate::error::ValidationError;
 
-#[derive(Debug, Deserialize)]
+use crate::types::{
+    AlbumId, ArtistId, DirectoryId, MusicFolderId, PlaylistId, PodcastChannelId, PodcastEpisodeId,
+    RadioStationId, ShareId, SongId, Strong,
+};
+
+// Music folder ids are integers in the Subsonic API, but strings everywhere else.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
+}
+
+fn music_folder_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MusicFolderId, D::Error> {
+    string_or_number(deserializer).map(MusicFolderId)
+}
+
+fn music_folder_ids<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<MusicFolderId>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Id(#[serde(deserialize_with = "music_folder_id")] MusicFolderId);
+
+    let ids = Option::<Vec<Id>>::deserialize(deserializer)?;
+
+    Ok(ids.map(|ids| ids.into_iter().map(|Id(id)| id).collect()))
+}
+
+#[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReplayGain {
     pub album_peak: Option<f64>,
@@ -32,7 +72,7 @@ pub struct AlbumListItem {
     pub genres: Option<Vec<String>>,
     pub is_video: Option<bool>,
     pub name: Option<String>,
-    pub parent: Option<String>,
+    pub parent: Option<DirectoryId>,
     pub replay_gain: Option<ReplayGain>,
     pub sort_name: Option<String>,
     pub year: Option<u64>,
@@ -42,12 +82,24 @@ pub struct AlbumListItem {
     pub media_type: Option<String>,
 }
 
+impl AlbumListItem {
+    pub fn directory_id(&self) -> Option<DirectoryId> {
+        self.is_dir.then(|| DirectoryId(self.id.get_ref().clone()))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlbumList {
     pub album: Option<Vec<AlbumListItem>>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumList2 {
+    pub album: Option<Vec<AlbumID3>>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlbumID3 {
@@ -71,12 +123,44 @@ pub struct AlbumID3 {
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct ArtistID3WithAlbums {
+    pub id: ArtistId,
+
+    // Required fields
+    pub name: String,
+
+    // Optional fields
+    pub album: Option<Vec<AlbumID3>>,
+    pub album_count: Option<u64>,
+    pub cover_art: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Genre {
+    // Song genres are named `name`, while getGenres calls it `value`.
+    #[serde(alias = "value")]
     pub name: String,
+
+    // Optional fields
+    pub album_count: Option<u64>,
+    pub song_count: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Genres {
+    pub genre: Option<Vec<Genre>>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct SongList {
+    pub song: Option<Vec<Song>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Song {
     pub id: SongId,
 
@@ -97,15 +181,21 @@ pub struct Song {
     pub created: Option<DateTime<Utc>>,
     pub disc_number: Option<u64>,
     pub duration: Option<u64>,
+    pub genre: Option<String>,
     pub genres: Option<Vec<Genre>>,
     pub is_video: Option<bool>,
-    pub parent: Option<String>,
+    pub music_brainz_id: Option<String>,
+    pub parent: Option<DirectoryId>,
     pub path: Option<String>,
+    pub play_count: Option<u64>,
+    pub played: Option<DateTime<Utc>>,
     pub replay_gain: Option<ReplayGain>,
     pub size: Option<u64>,
     pub sort_name: Option<String>,
+    pub starred: Option<DateTime<Utc>>,
     pub suffix: Option<String>,
     pub track: Option<u64>,
+    pub user_rating: Option<u64>,
     pub year: Option<u64>,
 
     // Renamed fields
@@ -113,6 +203,12 @@ pub struct Song {
     pub media_type: Option<String>,
 }
 
+impl Song {
+    pub fn directory_id(&self) -> Option<DirectoryId> {
+        self.is_dir.then(|| DirectoryId(self.id.get_ref().clone()))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlbumID3WithSongs {
@@ -121,6 +217,331 @@ pub struct AlbumID3WithSongs {
     pub song: Vec<Song>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicFolder {
+    #[serde(deserialize_with = "music_folder_id")]
+    pub id: MusicFolderId,
+
+    // Optional fields
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicFolders {
+    pub music_folder: Option<Vec<MusicFolder>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Artist {
+    pub id: DirectoryId,
+
+    // Required fields
+    pub name: String,
+
+    // Optional fields
+    pub artist_image_url: Option<String>,
+    pub average_rating: Option<f64>,
+    pub starred: Option<DateTime<Utc>>,
+    pub user_rating: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Index {
+    // Required fields
+    pub name: String,
+
+    // Optional fields
+    pub artist: Option<Vec<Artist>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Indexes {
+    // Required fields
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub last_modified: DateTime<Utc>,
+    pub ignored_articles: String,
+
+    // Optional fields
+    pub child: Option<Vec<Song>>,
+    pub index: Option<Vec<Index>>,
+    pub shortcut: Option<Vec<Artist>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicDirectory {
+    pub id: DirectoryId,
+
+    // Required fields
+    pub name: String,
+
+    // Optional fields
+    pub average_rating: Option<f64>,
+    pub child: Option<Vec<Song>>,
+    pub parent: Option<DirectoryId>,
+    pub play_count: Option<u64>,
+    pub starred: Option<DateTime<Utc>>,
+    pub user_rating: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bookmark {
+    // Required fields
+    pub changed: DateTime<Utc>,
+    pub created: DateTime<Utc>,
+    pub entry: Song,
+    pub position: u64,
+    pub username: String,
+
+    // Optional fields
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Bookmarks {
+    pub bookmark: Option<Vec<Bookmark>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternetRadioStation {
+    pub id: RadioStationId,
+
+    // Required fields
+    pub name: String,
+    pub stream_url: String,
+
+    // Optional fields
+    pub home_page_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternetRadioStations {
+    pub internet_radio_station: Option<Vec<InternetRadioStation>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JukeboxStatus {
+    // Required fields
+    pub current_index: i64,
+    pub gain: f32,
+    pub playing: bool,
+
+    // Optional fields
+    pub entry: Option<Vec<Song>>,
+    pub position: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LyricLine {
+    // Required fields
+    pub value: String,
+
+    // Optional fields
+    pub start: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuredLyrics {
+    // Required fields
+    pub lang: String,
+    pub synced: bool,
+
+    // Optional fields
+    pub display_artist: Option<String>,
+    pub display_title: Option<String>,
+    pub line: Option<Vec<LyricLine>>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricsList {
+    pub structured_lyrics: Option<Vec<StructuredLyrics>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClassicLyrics {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlist {
+    pub id: PlaylistId,
+
+    // Required fields
+    pub changed: DateTime<Utc>,
+    pub created: DateTime<Utc>,
+    pub duration: u64,
+    pub name: String,
+    pub song_count: u64,
+
+    // Optional fields
+    pub comment: Option<String>,
+    pub cover_art: Option<String>,
+    pub owner: Option<String>,
+    pub public: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlists {
+    pub playlist: Option<Vec<Playlist>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistWithSongs {
+    #[serde(flatten)]
+    pub playlist_data: Playlist,
+    pub entry: Option<Vec<Song>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult3 {
+    pub album: Option<Vec<AlbumID3>>,
+    pub song: Option<Vec<Song>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Starred2 {
+    pub album: Option<Vec<AlbumID3>>,
+    pub song: Option<Vec<Song>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayQueue {
+    // Required fields
+    pub changed: DateTime<Utc>,
+    pub changed_by: String,
+    pub username: String,
+
+    // Optional fields
+    pub current: Option<SongId>,
+    pub entry: Option<Vec<Song>>,
+    pub position: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PodcastStatus {
+    New,
+    Downloading,
+    Completed,
+    Error,
+    Deleted,
+    Skipped,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodcastEpisode {
+    pub id: PodcastEpisodeId,
+
+    // Required fields
+    pub status: PodcastStatus,
+    pub title: String,
+
+    // Optional fields
+    pub artist: Option<String>,
+    pub channel_id: Option<PodcastChannelId>,
+    pub content_type: Option<String>,
+    pub cover_art: Option<String>,
+    pub description: Option<String>,
+    pub duration: Option<u64>,
+    pub publish_date: Option<DateTime<Utc>>,
+    pub size: Option<u64>,
+    pub stream_id: Option<SongId>,
+    pub suffix: Option<String>,
+}
+
+impl PodcastEpisode {
+    /// The episode as a song the player can stream, once the server has downloaded it.
+    pub fn song(&self) -> Option<Song> {
+        let id = self.stream_id.clone()?;
+
+        Some(Song {
+            id,
+            is_dir: false,
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            content_type: self.content_type.clone(),
+            cover_art: self.cover_art.clone(),
+            duration: self.duration,
+            size: self.size,
+            suffix: self.suffix.clone(),
+            media_type: Some("podcast".to_owned()),
+            ..Song::default()
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodcastChannel {
+    pub id: PodcastChannelId,
+
+    // Required fields
+    pub status: PodcastStatus,
+    pub url: String,
+
+    // Optional fields
+    pub cover_art: Option<String>,
+    pub description: Option<String>,
+    pub episode: Option<Vec<PodcastEpisode>>,
+    pub error_message: Option<String>,
+    pub original_image_url: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Podcasts {
+    pub channel: Option<Vec<PodcastChannel>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewestPodcasts {
+    pub episode: Option<Vec<PodcastEpisode>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Share {
+    pub id: ShareId,
+
+    // Required fields
+    pub created: DateTime<Utc>,
+    pub url: String,
+    pub username: String,
+    pub visit_count: u64,
+
+    // Optional fields
+    pub description: Option<String>,
+    pub entry: Option<Vec<Song>>,
+    pub expires: Option<DateTime<Utc>>,
+    pub last_visited: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Shares {
+    pub share: Option<Vec<Share>>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScanStatus {
@@ -128,6 +549,167 @@ pub struct ScanStatus {
     pub count: i64,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Role {
+    Admin,
+    Settings,
+    Download,
+    Upload,
+    Playlist,
+    CoverArt,
+    Comment,
+    Podcast,
+    Stream,
+    Jukebox,
+    Share,
+    VideoConversion,
+}
+
+impl Role {
+    pub const ALL: [Role; 12] = [
+        Role::Admin,
+        Role::Settings,
+        Role::Download,
+        Role::Upload,
+        Role::Playlist,
+        Role::CoverArt,
+        Role::Comment,
+        Role::Podcast,
+        Role::Stream,
+        Role::Jukebox,
+        Role::Share,
+        Role::VideoConversion,
+    ];
+
+    /// The createUser and updateUser parameter for this role.
+    pub fn parameter(self) -> &'static str {
+        use Role::*;
+        match self {
+            Admin => "adminRole",
+            Settings => "settingsRole",
+            Download => "downloadRole",
+            Upload => "uploadRole",
+            Playlist => "playlistRole",
+            CoverArt => "coverArtRole",
+            Comment => "commentRole",
+            Podcast => "podcastRole",
+            Stream => "streamRole",
+            Jukebox => "jukeboxRole",
+            Share => "shareRole",
+            VideoConversion => "videoConversionRole",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        use Role::*;
+        match self {
+            Admin => "admin",
+            Settings => "settings",
+            Download => "download",
+            Upload => "upload",
+            Playlist => "playlist",
+            CoverArt => "cover-art",
+            Comment => "comment",
+            Podcast => "podcast",
+            Stream => "stream",
+            Jukebox => "jukebox",
+            Share => "share",
+            VideoConversion => "video-conversion",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.name())
+    }
+}
+
+impl FromStr for Role {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Role::ALL.iter().map(|role| role.name()).collect();
+
+                ValidationError(format!(
+                    "Unknown role {s}, expected one of {}.",
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    // Required fields
+    pub username: String,
+    pub scrobbling_enabled: bool,
+    pub admin_role: bool,
+    pub settings_role: bool,
+    pub download_role: bool,
+    pub upload_role: bool,
+    pub playlist_role: bool,
+    pub cover_art_role: bool,
+    pub comment_role: bool,
+    pub podcast_role: bool,
+    pub stream_role: bool,
+    pub jukebox_role: bool,
+    pub share_role: bool,
+
+    // Optional fields
+    pub avatar_last_changed: Option<DateTime<Utc>>,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "music_folder_ids")]
+    pub folder: Option<Vec<MusicFolderId>>,
+    pub max_bit_rate: Option<u64>,
+    pub video_conversion_role: Option<bool>,
+}
+
+impl User {
+    pub fn has_role(&self, role: Role) -> bool {
+        use Role::*;
+        match role {
+            Admin => self.admin_role,
+            Settings => self.settings_role,
+            Download => self.download_role,
+            Upload => self.upload_role,
+            Playlist => self.playlist_role,
+            CoverArt => self.cover_art_role,
+            Comment => self.comment_role,
+            Podcast => self.podcast_role,
+            Stream => self.stream_role,
+            Jukebox => self.jukebox_role,
+            Share => self.share_role,
+            VideoConversion => self.video_conversion_role.unwrap_or_default(),
+        }
+    }
+
+    pub fn roles(&self) -> Vec<Role> {
+        Role::ALL
+            .into_iter()
+            .filter(|role| self.has_role(*role))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Users {
+    pub user: Option<Vec<User>>,
+}
+
+#[derive(Debug, Deserialize, Error)]
+#[serde(rename_all = "camelCase")]
+#[error("Subsonic error {code}: {}", message.as_deref().unwrap_or("no message"))]
+pub struct SubsonicError {
+    pub code: i64,
+    pub message: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubsonicResponse {
@@ -140,7 +722,34 @@ pub struct SubsonicResponse {
     // Optional fields
     pub album: Option<AlbumID3WithSongs>,
     pub album_list: Option<AlbumList>,
+    pub album_list2: Option<AlbumList2>,
+    pub artist: Option<ArtistID3WithAlbums>,
+    pub bookmarks: Option<Bookmarks>,
+    pub directory: Option<MusicDirectory>,
+    pub error: Option<SubsonicError>,
+    pub genres: Option<Genres>,
+    pub indexes: Option<Indexes>,
+    pub internet_radio_stations: Option<InternetRadioStations>,
+    pub jukebox_playlist: Option<JukeboxStatus>,
+    pub jukebox_status: Option<JukeboxStatus>,
+    pub lyrics: Option<ClassicLyrics>,
+    pub lyrics_list: Option<LyricsList>,
+    pub music_folders: Option<MusicFolders>,
+    pub newest_podcasts: Option<NewestPodcasts>,
+    pub play_queue: Option<PlayQueue>,
+    pub playlist: Option<PlaylistWithSongs>,
+    pub playlists: Option<Playlists>,
+    pub podcasts: Option<Podcasts>,
+    pub random_songs: Option<SongList>,
     pub scan_status: Option<ScanStatus>,
+    pub search_result3: Option<SearchResult3>,
+    pub shares: Option<Shares>,
+    pub similar_songs2: Option<SongList>,
+    pub songs_by_genre: Option<SongList>,
+    pub starred2: Option<Starred2>,
+    pub top_songs: Option<SongList>,
+    pub user: Option<User>,
+    pub users: Option<Users>,
 
     // Renamed fields
     #[serde(rename = "type")]
@@ -152,3 +761,253 @@ pub struct SubsonicResponse {
 pub struct OuterSubsonicResponse {
     pub subsonic_response: SubsonicResponse,
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::{json, Value};
+
+    use crate::error::OnMissing;
+
+    use super::*;
+
+    /// Wraps `payload` in the envelope that every response from the server comes in.
+    fn response_from(payload: Value) -> Result<SubsonicResponse> {
+        let mut response = json!({
+            "status": "ok",
+            "version": "1.16.1",
+            "type": "navidrome",
+            "serverVersion": "0.51.1",
+            "openSubsonic": true
+        });
+
+        if let (Some(response), Value::Object(payload)) = (response.as_object_mut(), payload) {
+            response.extend(payload);
+        }
+
+        let outer: OuterSubsonicResponse =
+            serde_json::from_value(json!({ "subsonic-response": response }))?;
+
+        Ok(outer.subsonic_response)
+    }
+
+    #[test]
+    fn test_music_folders() -> Result<()> {
+        let response = response_from(json!({
+            "musicFolders": {
+                "musicFolder": [
+                    { "id": 1, "name": "Music" },
+                    { "id": "classical", "name": "Classical" }
+                ]
+            }
+        }))?;
+
+        let ids: Vec<_> = response
+            .music_folders
+            .and_then(|folders| folders.music_folder)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|folder| folder.id)
+            .collect();
+
+        assert_eq!(
+            ids,
+            vec![
+                MusicFolderId::unchecked("1"),
+                MusicFolderId::unchecked("classical")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_genres() -> Result<()> {
+        let response = response_from(json!({
+            "genres": {
+                "genre": [
+                    { "value": "Baroque", "songCount": 120, "albumCount": 9 },
+                    { "value": "Jazz", "songCount": 40, "albumCount": 4 }
+                ]
+            }
+        }))?;
+
+        let genres = response
+            .genres
+            .on_missing("genres")?
+            .genre
+            .on_missing("genre")?;
+
+        assert_eq!(genres[0].name, "Baroque");
+        assert_eq!(genres[0].song_count, Some(120));
+        assert_eq!(genres[0].album_count, Some(9));
+
+        let song: Song = serde_json::from_str(
+            r#"{ "id": "s-1", "isDir": false, "title": "Aria", "genres": [{ "name": "Baroque" }] }"#,
+        )?;
+
+        let song_genres = song.genres.on_missing("genres")?;
+
+        assert_eq!(song_genres[0].name, "Baroque");
+        assert_eq!(song_genres[0].song_count, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexes() -> Result<()> {
+        let response = response_from(json!({
+            "indexes": {
+                "lastModified": 1709251200000_i64,
+                "ignoredArticles": "The El La Los Las Le Les",
+                "index": [
+                    {
+                        "name": "B",
+                        "artist": [{ "id": "dir-bach", "name": "Bach, Johann Sebastian" }]
+                    }
+                ]
+            }
+        }))?;
+
+        let indexes = response.indexes.on_missing("indexes")?;
+
+        assert_eq!(indexes.last_modified.timestamp_millis(), 1709251200000);
+
+        let index = &indexes.index.on_missing("index")?[0];
+        let artist = &index.artist.as_ref().on_missing("artist")?[0];
+
+        assert_eq!(index.name, "B");
+        assert_eq!(artist.id, DirectoryId::unchecked("dir-bach"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_music_directory() -> Result<()> {
+        let response = response_from(json!({
+            "directory": {
+                "id": "dir-bach",
+                "name": "Bach, Johann Sebastian",
+                "parent": "root",
+                "child": [
+                    { "id": "dir-goldberg", "parent": "dir-bach", "isDir": true, "title": "Goldberg Variations" },
+                    { "id": "song-aria", "parent": "dir-bach", "isDir": false, "title": "Aria", "duration": 287 }
+                ]
+            }
+        }))?;
+
+        let directory = response.directory.on_missing("directory")?;
+        let children = directory.child.on_missing("child")?;
+
+        assert_eq!(directory.parent, Some(DirectoryId::unchecked("root")));
+        assert_eq!(
+            children[0].directory_id(),
+            Some(DirectoryId::unchecked("dir-goldberg"))
+        );
+        assert_eq!(children[1].directory_id(), None);
+        assert_eq!(children[1].parent, Some(DirectoryId::unchecked("dir-bach")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_podcasts() -> Result<()> {
+        let response = response_from(json!({
+            "podcasts": {
+                "channel": [{
+                    "id": "pc-1",
+                    "url": "https://podcast.example.com/feed.xml",
+                    "title": "Early Music Hour",
+                    "status": "completed",
+                    "episode": [
+                        {
+                            "id": "ep-1",
+                            "streamId": "song-ep-1",
+                            "channelId": "pc-1",
+                            "title": "Dufay",
+                            "status": "completed",
+                            "publishDate": "2024-03-01T06:00:00Z",
+                            "duration": 3540
+                        },
+                        { "id": "ep-2", "channelId": "pc-1", "title": "Machaut", "status": "skipped" }
+                    ]
+                }]
+            }
+        }))?;
+
+        let channels = response
+            .podcasts
+            .and_then(|podcasts| podcasts.channel)
+            .on_missing("channel")?;
+        let episodes = channels[0].episode.as_ref().on_missing("episode")?;
+
+        let song = episodes[0].song().on_missing("song")?;
+
+        assert_eq!(song.id, SongId::unchecked("song-ep-1"));
+        assert_eq!(song.title, "Dufay");
+        assert_eq!(song.duration, Some(3540));
+
+        assert_eq!(episodes[1].status, PodcastStatus::Skipped);
+        assert!(episodes[1].song().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_user() -> Result<()> {
+        let response = response_from(json!({
+            "user": {
+                "username": "ana",
+                "email": "ana@example.com",
+                "scrobblingEnabled": true,
+                "maxBitRate": 320,
+                "adminRole": false,
+                "settingsRole": true,
+                "downloadRole": true,
+                "uploadRole": false,
+                "playlistRole": true,
+                "coverArtRole": false,
+                "commentRole": false,
+                "podcastRole": false,
+                "streamRole": true,
+                "jukeboxRole": false,
+                "shareRole": false,
+                "folder": [1, "classical"]
+            }
+        }))?;
+
+        let user = response.user.on_missing("user")?;
+
+        assert_eq!(
+            user.roles(),
+            vec![Role::Settings, Role::Download, Role::Playlist, Role::Stream]
+        );
+        assert_eq!(
+            user.folder,
+            Some(vec![
+                MusicFolderId::unchecked("1"),
+                MusicFolderId::unchecked("classical")
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_names() {
+        for role in Role::ALL {
+            assert_eq!(role.to_string().parse(), Ok(role));
+            assert_eq!(
+                <Role as ValueEnum>::from_str(&role.to_string(), false),
+                Ok(role)
+            );
+        }
+
+        assert_eq!(
+            "owner".parse::<Role>(),
+            Err(ValidationError(
+                "Unknown role owner, expected one of admin, settings, download, upload, playlist, cover-art, comment, podcast, stream, jukebox, share, video-conversion.".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::env;
+
+use anyhow::{Context, Result};
+
+use knuckles::config::{default_config_file_path, system_config_file_path};
+use knuckles::layers::ConfigLayers;
+
+use super::Cli;
+
+pub fn load_config_layers(cli: &Cli) -> Result<ConfigLayers> {
+    let mut layers = ConfigLayers::new()?;
+
+    if let Some(system_config_path) = system_config_file_path() {
+        layers.add_file_if_exists(&system_config_path)?;
+    }
+
+    match &cli.config {
+        Some(config_path) => layers.add_file(config_path)?,
+        None => layers.add_file_if_exists(&default_config_file_path()?)?,
+    }
+
+    layers.add_environment(env::vars())?;
+
+    for assignment in &cli.overrides {
+        let (key, value) = assignment
+            .split_once('=')
+            .with_context(|| format!("Expected KEY=VALUE for --set, got `{assignment}`."))?;
+
+        layers.add_override("--set", key, value)?;
+    }
+
+    if let Some(profile) = &cli.profile {
+        layers.add_override("--profile", "default_server", profile)?;
+    }
+
+    Ok(layers)
+}
+
+pub fn show_config(layers: &ConfigLayers, resolved: bool) {
+    if resolved {
+        for value in layers.resolved() {
+            match value.line {
+                Some(line) => println!(
+                    "{} = {}  # {}, line {line}",
+                    value.key, value.value, value.source
+                ),
+                None => println!("{} = {}  # {}", value.key, value.value, value.source),
+            }
+        }
+
+        return;
+    }
+
+    for layer in layers.layers() {
+        let values = layer.redacted();
+
+        if values.is_empty() {
+            continue;
+        }
+
+        println!("# {}", layer.source);
+
+        for (key, value) in values {
+            println!("{key} = {value}");
+        }
+
+        println!();
+    }
+}
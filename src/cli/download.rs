@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use knuckles::api_types::Song;
+use knuckles::client::SubsonicClient;
+use knuckles::download::{download_song, FileNameTemplate, Outcome};
+use knuckles::snapshot::Snapshot;
+use knuckles::types::{AlbumId, ArtistId, PlaylistId};
+
+pub struct Source<'a> {
+    pub album: Option<&'a str>,
+    pub artist: Option<&'a str>,
+    pub playlist: Option<&'a str>,
+}
+
+async fn songs(client: &SubsonicClient, source: &Source<'_>) -> Result<Vec<Song>> {
+    if let Some(album) = source.album {
+        return Ok(client.album(&AlbumId::unchecked(album)).await?.song);
+    }
+
+    if let Some(artist) = source.artist {
+        let albums = client
+            .artist(&ArtistId::unchecked(artist))
+            .await?
+            .album
+            .unwrap_or_default();
+
+        let mut songs = Vec::new();
+
+        for album in albums {
+            songs.extend(client.album(&album.id).await?.song);
+        }
+
+        return Ok(songs);
+    }
+
+    let playlist = source.playlist.context("Nothing to download.")?;
+
+    Ok(client
+        .playlist(&PlaylistId::unchecked(playlist))
+        .await?
+        .entry
+        .unwrap_or_default())
+}
+
+/// Records where songs were saved, relative to `directory`, so they play from there.
+pub fn remember_local_files(
+    snapshot: &mut Snapshot,
+    directory: &Path,
+    saved: &[(PathBuf, Song)],
+) -> Result<()> {
+    if saved.is_empty() {
+        return Ok(());
+    }
+
+    let directory = directory.canonicalize()?;
+
+    let songs: Vec<_> = saved.iter().map(|(_, song)| song.clone()).collect();
+    snapshot.store_songs(&songs)?;
+
+    for (path, song) in saved {
+        snapshot.set_local_file(&song.id, &directory.join(path))?;
+    }
+
+    Ok(())
+}
+
+pub async fn download(
+    client: &SubsonicClient,
+    snapshot: &mut Snapshot,
+    source: &Source<'_>,
+    directory: &Path,
+    template: &str,
+) -> Result<()> {
+    let template = FileNameTemplate::new(template).context("Invalid download template.")?;
+
+    let mut saved = Vec::new();
+    let mut failed = 0;
+
+    for song in songs(client, source).await? {
+        if song.is_dir {
+            continue;
+        }
+
+        let relative = template.path_for(&song);
+        let path = directory.join(&relative);
+
+        match download_song(client, &song, &path).await {
+            Ok(Outcome::Downloaded) => println!("Downloaded {}", path.display()),
+            Ok(Outcome::Resumed) => println!("Resumed {}", path.display()),
+            Ok(Outcome::Skipped) => println!("Skipped {}, already complete", path.display()),
+            Err(e) => {
+                eprintln!("Could not download {}: {e:#}", path.display());
+                failed += 1;
+                continue;
+            }
+        }
+
+        saved.push((relative, song));
+    }
+
+    // Songs that did download still play offline, even if others failed.
+    remember_local_files(snapshot, directory, &saved)?;
+
+    if failed > 0 {
+        bail!("Could not download {failed} songs, run the download again to retry them.");
+    }
+
+    Ok(())
+}
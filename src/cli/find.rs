@@ -0,0 +1,47 @@
+use anyhow::Result;
+
+use knuckles::player::Player;
+use knuckles::search::{Query, SearchIndex};
+use knuckles::snapshot::{fetch_albums, Snapshot};
+
+pub async fn find(
+    player: &Player,
+    snapshot: &mut Snapshot,
+    query: &[String],
+    limit: usize,
+) -> Result<()> {
+    let query: Query = query.join(" ").parse()?;
+
+    let mut songs = snapshot.all_songs()?;
+
+    // The index is built from the library snapshot, fetching it once if there is none.
+    if songs.is_empty() {
+        eprintln!("Fetching the library to index it, later searches use the local copy.");
+
+        let albums = fetch_albums(player.client()).await?;
+        snapshot.store(&albums, true, None)?;
+
+        songs = snapshot.all_songs()?;
+    }
+
+    let index = SearchIndex::new(songs);
+
+    for song in index.search(&query, limit) {
+        let year = song.year.map(|year| year.to_string()).unwrap_or_default();
+        let saved = if player.is_saved_locally(song) {
+            "*"
+        } else {
+            " "
+        };
+
+        println!(
+            "{} {saved} {} - {} - {} {year}",
+            song.id,
+            song.artist.as_deref().unwrap_or("Unknown"),
+            song.album.as_deref().unwrap_or("Unknown"),
+            song.title
+        );
+    }
+
+    Ok(())
+}
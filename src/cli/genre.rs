@@ -0,0 +1,54 @@
+use anyhow::{bail, Result};
+
+use knuckles::client::MAX_SONGS_BY_GENRE_COUNT;
+use knuckles::player::Player;
+
+use super::prompt::prompt_until;
+
+pub async fn play_genre(player: &mut Player, name: Option<&str>) -> Result<()> {
+    let name = match name {
+        Some(name) => name.to_owned(),
+        None => pick_genre(player).await?,
+    };
+
+    let songs = player
+        .client()
+        .songs_by_genre(&name, Some(MAX_SONGS_BY_GENRE_COUNT), None, None)
+        .await?;
+
+    if songs.is_empty() {
+        bail!("No songs found for genre {name}.");
+    }
+
+    println!("Playing {} songs from {name}.", songs.len());
+
+    player.enqueue(songs);
+    player.play().await
+}
+
+async fn pick_genre(player: &Player) -> Result<String> {
+    let mut genres = player.client().genres().await?;
+
+    if genres.is_empty() {
+        bail!("The server has no genres.");
+    }
+
+    genres.sort_by_key(|genre| genre.name.to_lowercase());
+
+    for (number, genre) in genres.iter().enumerate() {
+        println!(
+            "{:>4}. {} ({} albums, {} songs)",
+            number + 1,
+            genre.name,
+            genre.album_count.unwrap_or_default(),
+            genre.song_count.unwrap_or_default()
+        );
+    }
+
+    let index = prompt_until("Genre number", |answer| match answer.parse::<usize>() {
+        Ok(number) if (1..=genres.len()).contains(&number) => Ok(number - 1),
+        _ => Err(format!("Enter a number between 1 and {}.", genres.len())),
+    })?;
+
+    Ok(genres.swap_remove(index).name)
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+
+use knuckles::config::{
+    config_has_server, default_config_file_path, make_client, write_server_config, AuthInfo,
+    SubsonicConfig,
+};
+use knuckles::hash::default_hasher;
+use knuckles::types::{Password, ServerUrl, Username};
+
+use super::prompt::{confirm, prompt_secret, prompt_until};
+use super::Cli;
+
+pub async fn init(cli: &Cli) -> Result<()> {
+    let config_path = match &cli.config {
+        Some(config_path) => config_path.clone(),
+        None => default_config_file_path()?,
+    };
+
+    let profile = cli.profile.as_deref();
+
+    if config_has_server(&config_path, profile)? {
+        let section = match profile {
+            Some(name) => format!("[servers.{name}]"),
+            None => "[client]".to_owned(),
+        };
+
+        let question = format!(
+            "{} already has a {section} section. Replace it?",
+            config_path.display()
+        );
+
+        if !confirm(&question, false)? {
+            return Ok(());
+        }
+    }
+
+    let url = prompt_until("Server URL", ServerUrl::new)?;
+    let username = prompt_until("Username", Username::new)?;
+    let password = Password(prompt_secret("Password")?);
+
+    let mut hasher = default_hasher();
+
+    let auth_info = if confirm("Store a salted token instead of the password?", false)? {
+        AuthInfo::Token(hasher.md5_with_random_salt(&password))
+    } else {
+        AuthInfo::Password(password)
+    };
+
+    let server = SubsonicConfig {
+        url,
+        username,
+        auth_info,
+    };
+
+    let client = make_client(&server, &mut hasher)?;
+
+    match client.ping().await {
+        Ok(_) => println!("Connected to {} as {}.", server.url, server.username),
+        Err(e) => {
+            eprintln!("Could not log in to {}: {e}", server.url);
+
+            if !confirm("Save the configuration anyway?", false)? {
+                return Ok(());
+            }
+        }
+    }
+
+    write_server_config(&config_path, profile, &server)?;
+
+    println!("Wrote {}.", config_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+
+use knuckles::api_types::JukeboxStatus;
+use knuckles::client::JukeboxAction;
+use knuckles::player::Player;
+use knuckles::types::SongId;
+
+use super::JukeboxCommand;
+
+pub fn parse_gain(value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|gain| (0.0..=1.0).contains(gain))
+        .ok_or_else(|| format!("Expected a volume from 0.0 to 1.0, got {value}."))
+}
+
+fn song_ids(songs: &[String]) -> Vec<SongId> {
+    songs
+        .iter()
+        .map(|id| SongId::unchecked(id.as_str()))
+        .collect()
+}
+
+fn print_status(status: &JukeboxStatus) {
+    let state = if status.playing { "Playing" } else { "Stopped" };
+    let position = status.position.unwrap_or_default();
+
+    println!(
+        "{state} at song {}, {}:{:02}, gain {:.2}",
+        status.current_index,
+        position / 60,
+        position % 60,
+        status.gain
+    );
+}
+
+pub async fn jukebox(player: &Player, command: &JukeboxCommand) -> Result<()> {
+    let action = match command {
+        JukeboxCommand::Status => JukeboxAction::Status,
+        JukeboxCommand::List => JukeboxAction::Get,
+        JukeboxCommand::Set { songs } => JukeboxAction::Set(song_ids(songs)),
+        JukeboxCommand::Add { songs } => JukeboxAction::Add(song_ids(songs)),
+        JukeboxCommand::Remove { index } => JukeboxAction::Remove(*index),
+        JukeboxCommand::Clear => JukeboxAction::Clear,
+        JukeboxCommand::Shuffle => JukeboxAction::Shuffle,
+        JukeboxCommand::Start => JukeboxAction::Start,
+        JukeboxCommand::Stop => JukeboxAction::Stop,
+        JukeboxCommand::Skip { index, offset } => JukeboxAction::Skip {
+            index: *index,
+            offset: *offset,
+        },
+        JukeboxCommand::Gain { gain } => JukeboxAction::SetGain(*gain),
+    };
+
+    let status = player.client().jukebox_control(&action).await?;
+
+    print_status(&status);
+
+    for (index, song) in status.entry.iter().flatten().enumerate() {
+        let marker = if index as i64 == status.current_index {
+            ">"
+        } else {
+            " "
+        };
+
+        println!("{marker}{index:>4}. {}  {}", song.title, song.id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anyhow::{bail, Result};
+
+use knuckles::api_types::{AlbumID3, Song};
+use knuckles::player::Player;
+use knuckles::snapshot::{refresh, Snapshot, SnapshotArtist};
+use knuckles::types::{AlbumId, ArtistId};
+
+use super::find::find;
+use super::LibraryCommand;
+
+pub async fn library(
+    player: &mut Player,
+    snapshot: &mut Snapshot,
+    command: &LibraryCommand,
+) -> Result<()> {
+    match command {
+        LibraryCommand::Refresh { full } => {
+            let albums = refresh(player.client(), snapshot, *full).await?;
+
+            println!("Fetched {albums} albums.");
+        }
+        LibraryCommand::Artists => print_artists(&snapshot.all_artists()?),
+        LibraryCommand::Albums { artist } => {
+            print_albums(&snapshot.artist_albums(&ArtistId::unchecked(artist.as_str()))?);
+        }
+        LibraryCommand::Songs { album } => {
+            print_songs(
+                player,
+                &snapshot.album_songs(&AlbumId::unchecked(album.as_str()))?,
+            );
+        }
+        LibraryCommand::Search { query, limit } => {
+            return find(player, snapshot, query, *limit).await;
+        }
+        LibraryCommand::Play { album } => {
+            let songs = snapshot.album_songs(&AlbumId::unchecked(album.as_str()))?;
+
+            if songs.is_empty() {
+                bail!("No album {album} in the library snapshot, try `library refresh`.");
+            }
+
+            if let Err(e) = player.client().ping().await {
+                eprintln!("The server is unreachable, only playing songs saved locally: {e}");
+                player.set_offline(true);
+            }
+
+            player.enqueue(songs);
+
+            return player.play().await;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_artists(artists: &[SnapshotArtist]) {
+    for artist in artists {
+        println!(
+            "{}  {} ({} albums)",
+            artist.id, artist.name, artist.album_count
+        );
+    }
+}
+
+fn print_albums(albums: &[AlbumID3]) {
+    for album in albums {
+        let year = album.year.map(|year| year.to_string()).unwrap_or_default();
+
+        println!(
+            "{}  {year:>4}  {} - {}",
+            album.id,
+            album.artist.as_deref().unwrap_or("Unknown"),
+            album.name
+        );
+    }
+}
+
+fn print_songs(player: &Player, songs: &[Song]) {
+    for song in songs {
+        let saved = if player.is_saved_locally(song) {
+            "*"
+        } else {
+            " "
+        };
+
+        println!(
+            "{} {saved} {} - {}",
+            song.id,
+            song.artist.as_deref().unwrap_or("Unknown"),
+            song.title
+        );
+    }
+}
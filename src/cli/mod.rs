@@ -0,0 +1,525 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use knuckles::api_types::Role;
+use knuckles::playlist::PlaylistFormat;
+use knuckles::stats::{Period, ReportFormat};
+
+pub mod config;
+pub mod download;
+pub mod find;
+pub mod genre;
+pub mod init;
+pub mod jukebox;
+pub mod library;
+pub mod playlist;
+pub mod podcast;
+pub mod prompt;
+pub mod queue;
+pub mod radio;
+pub mod share;
+pub mod smart;
+pub mod station;
+pub mod stats;
+pub mod sync;
+pub mod user;
+
+/// Parses a date (midnight UTC) or an RFC 3339 time.
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+        .ok_or_else(|| format!("Expected a date like 2024-12-31 or an RFC 3339 time, got {value}."))
+}
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Server profile to use, as named in a [servers.<name>] section.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Configuration file to read instead of the default user file.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Override a configuration value, e.g. `--set client.url=https://example.com`.
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<String>,
+
+    /// Show lyrics while playing, line by line when the server has synced lyrics.
+    #[arg(long, global = true)]
+    pub lyrics: bool,
+
+    /// Play on the server's jukebox instead of this computer's speakers.
+    #[arg(long, global = true)]
+    pub jukebox: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Save original files of an album, artist or playlist.
+    #[command(group(ArgGroup::new("source").required(true)))]
+    Download {
+        #[arg(long, group = "source", value_name = "ALBUM_ID")]
+        album: Option<String>,
+        #[arg(long, group = "source", value_name = "ARTIST_ID")]
+        artist: Option<String>,
+        #[arg(long, group = "source", value_name = "PLAYLIST_ID")]
+        playlist: Option<String>,
+        /// Directory to save into.
+        #[arg(long, default_value = ".")]
+        to: PathBuf,
+        /// File name template, e.g. `{artist}/{album}/{track} {title}.{suffix}`, instead of
+        /// download.template from the configuration.
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Fuzzy find songs in a local index of the library, marking those saved locally
+    /// with `*`.
+    Find {
+        /// Words to find in titles, artists and albums, and filters such as
+        /// `artist:"miles davis"`, `album:`, `title:`, `genre:` and `year:>2010`. Filters
+        /// match text anywhere in the field, ignoring case and accents.
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Maximum number of songs to list.
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Write a server configuration interactively, checking it against the server first.
+    Init,
+    /// Play songs from a genre, picking it from the server's list if no name is given.
+    Genre {
+        /// Genre name, as listed by the server.
+        name: Option<String>,
+    },
+    /// Control the server's jukebox directly.
+    #[command(subcommand)]
+    Jukebox(JukeboxCommand),
+    /// Browse, search and play from a local snapshot of the library, which works while
+    /// the server is unreachable.
+    #[command(subcommand)]
+    Library(LibraryCommand),
+    /// Export playlists to files and import them from files.
+    #[command(subcommand)]
+    Playlist(PlaylistCommand),
+    /// Manage and play podcasts hosted by the server.
+    #[command(subcommand)]
+    Podcast(PodcastCommand),
+    /// Create and manage public share links.
+    #[command(subcommand)]
+    Share(ShareCommand),
+    /// Play or save the smart playlists defined in [smart_playlists.<name>] sections.
+    ///
+    /// Rules see play counts and stars as they are now, so every album is fetched from the
+    /// server each time, which takes a while on a large library.
+    #[command(subcommand)]
+    Smart(SmartCommand),
+    /// Manage and play the server's internet radio stations.
+    #[command(subcommand)]
+    Station(StationCommand),
+    /// Report top artists, albums and genres, listening time and how often playback was
+    /// stopped early, from the history of songs played on this computer.
+    ///
+    /// A play counts as stopped early when knuckles was quit before four minutes, or half
+    /// of the song, had been heard.
+    Stats {
+        /// Only count plays in this year, for a yearly report.
+        #[arg(long, conflicts_with_all = ["since", "until"])]
+        year: Option<i32>,
+        /// Only count plays from this time on, as a date (midnight UTC) or RFC 3339 time.
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// Only count plays before this time, as a date (midnight UTC) or RFC 3339 time.
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Total listening time by day, week, month or year.
+        #[arg(long, value_enum, default_value_t = Period::Month)]
+        period: Period,
+        /// Number of top artists, albums and genres to list.
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Write the report as json or csv instead of text.
+        #[arg(long)]
+        format: Option<ReportFormat>,
+    },
+    /// Mirror playlists, starred music and album lists into a directory, deleting songs
+    /// no longer selected.
+    #[command(group(ArgGroup::new("selector").required(true).multiple(true)))]
+    Sync {
+        /// Directory to keep in sync.
+        #[arg(long)]
+        to: PathBuf,
+        /// Playlist to include, repeat for several.
+        #[arg(long = "playlist", group = "selector", value_name = "PLAYLIST_ID")]
+        playlists: Vec<String>,
+        /// Include starred songs and albums.
+        #[arg(long, group = "selector")]
+        starred: bool,
+        /// Album list to include, repeat for several.
+        #[arg(long = "album-list", group = "selector", value_name = "TYPE")]
+        album_lists: Vec<SyncAlbumList>,
+        /// Number of albums to take from each album list.
+        #[arg(long, default_value_t = 20)]
+        album_list_size: u64,
+        /// File name template instead of download.template from the configuration.
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Administer the server's users.
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Play random songs, then keep the queue filled with similar songs.
+    Radio {
+        /// Start from songs similar to this artist instead of random songs.
+        #[arg(long, value_name = "ARTIST_ID")]
+        artist: Option<String>,
+        /// Only pick random songs from this genre.
+        #[arg(long)]
+        genre: Option<String>,
+        /// Only pick random songs from this year or later.
+        #[arg(long)]
+        from_year: Option<u64>,
+        /// Only pick random songs from this year or earlier.
+        #[arg(long)]
+        to_year: Option<u64>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SyncAlbumList {
+    Newest,
+    Recent,
+    Frequent,
+    Highest,
+    Random,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the configuration, with secrets redacted.
+    Show {
+        /// Print the effective configuration and where each value came from.
+        #[arg(long)]
+        resolved: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PlaylistCommand {
+    /// List playlists.
+    List,
+    /// Write a playlist as M3U8, XSPF or JSPF.
+    Export {
+        /// Playlist ID, as listed by `playlist list`.
+        playlist: String,
+        /// File to write instead of standard output.
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Format to write, instead of going by the output file's extension. Defaults to
+        /// m3u8 on standard output.
+        #[arg(long)]
+        format: Option<PlaylistFormat>,
+        /// Put before each song's path on the server, e.g. the directory the music is in.
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Create a playlist from an M3U8, XSPF or JSPF file, matching its entries to songs
+    /// by MusicBrainz ID, path, or artist and title.
+    Import {
+        file: PathBuf,
+        /// Playlist name, instead of the name in the file or the file name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Format to read, instead of going by the file's extension.
+        #[arg(long)]
+        format: Option<PlaylistFormat>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PodcastCommand {
+    /// List podcast channels, or the episodes of one channel.
+    List {
+        /// Channel ID to list episodes for.
+        channel: Option<String>,
+    },
+    /// List the newest episodes across all channels.
+    Newest {
+        /// Maximum number of episodes to list.
+        #[arg(long)]
+        count: Option<u64>,
+    },
+    /// Ask the server to check all channels for new episodes.
+    Refresh,
+    /// Subscribe to a podcast feed.
+    Add {
+        /// URL of the podcast feed.
+        url: String,
+    },
+    /// Unsubscribe from a channel and delete its episodes.
+    Remove {
+        /// Channel ID, as listed by `podcast list`.
+        channel: String,
+    },
+    /// Ask the server to download an episode.
+    Download {
+        /// Episode ID, as listed by `podcast list CHANNEL`.
+        episode: String,
+    },
+    /// Delete a downloaded episode from the server.
+    Delete {
+        /// Episode ID, as listed by `podcast list CHANNEL`.
+        episode: String,
+    },
+    /// Play downloaded episodes.
+    Play {
+        /// Episode IDs, as listed by `podcast list CHANNEL`.
+        #[arg(required = true)]
+        episodes: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LibraryCommand {
+    /// Update the snapshot from the server, only fetching new and changed albums unless
+    /// `--full`.
+    Refresh {
+        /// Fetch every album again, dropping albums deleted from the server.
+        #[arg(long)]
+        full: bool,
+    },
+    /// List artists.
+    Artists,
+    /// List an artist's albums.
+    Albums {
+        /// Artist ID, as listed by `library artists`.
+        artist: String,
+    },
+    /// List an album's songs, marking those saved locally with `*`.
+    Songs {
+        /// Album ID, as listed by `library albums ARTIST`.
+        album: String,
+    },
+    /// Fuzzy find songs in the library snapshot, the same search as `find`.
+    Search {
+        /// Words and filters, as for `find`.
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Maximum number of songs to list.
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Play an album, using local files where saved.
+    Play {
+        /// Album ID, as listed by `library albums ARTIST`.
+        album: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SmartCommand {
+    /// List smart playlists and their rules.
+    List,
+    /// List the songs a smart playlist selects now.
+    Show {
+        /// Smart playlist, as named in its [smart_playlists.<name>] section.
+        name: String,
+    },
+    /// Play the songs a smart playlist selects now.
+    Play {
+        /// Smart playlist, as named in its [smart_playlists.<name>] section.
+        name: String,
+    },
+    /// Replace the songs of server playlists with what their rules select now, creating
+    /// the playlists if needed.
+    Save {
+        /// Smart playlists to save, all of them if none are named.
+        names: Vec<String>,
+        /// Keep running and save again at this interval, e.g. `30m`, `6h` or `1d`. Each
+        /// save fetches every album from the server, so keep it long for large libraries.
+        #[arg(long, value_parser = smart::parse_interval)]
+        every: Option<Duration>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum StationCommand {
+    /// List internet radio stations.
+    List,
+    /// Add an internet radio station.
+    Add {
+        name: String,
+        /// URL of the audio stream.
+        url: String,
+        /// URL of the station's website.
+        #[arg(long)]
+        homepage: Option<String>,
+    },
+    /// Change an internet radio station.
+    Update {
+        /// Station ID, as listed by `station list`.
+        station: String,
+        name: String,
+        /// URL of the audio stream.
+        url: String,
+        /// URL of the station's website.
+        #[arg(long)]
+        homepage: Option<String>,
+    },
+    /// Remove an internet radio station.
+    Remove {
+        /// Station ID, as listed by `station list`.
+        station: String,
+    },
+    /// Play an internet radio station.
+    Play {
+        /// Station ID or name, as listed by `station list`.
+        station: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ShareCommand {
+    /// List shares with their public URLs.
+    List,
+    /// Share songs, albums or directories and print the public URL.
+    Create {
+        /// IDs of the songs, albums or directories to share.
+        #[arg(required = true)]
+        ids: Vec<String>,
+        #[arg(long)]
+        description: Option<String>,
+        /// When the share stops working, as a date (midnight UTC) or RFC 3339 time.
+        #[arg(long, value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+    },
+    /// Change the description or expiry of a share.
+    Update {
+        /// Share ID, as listed by `share list`.
+        share: String,
+        #[arg(long)]
+        description: Option<String>,
+        /// When the share stops working, as a date (midnight UTC) or RFC 3339 time.
+        #[arg(long, value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+    },
+    /// Delete a share, disabling its public URL.
+    Delete {
+        /// Share ID, as listed by `share list`.
+        share: String,
+    },
+}
+
+#[derive(Args)]
+pub struct UserSettingsArgs {
+    #[arg(long)]
+    pub email: Option<String>,
+    /// Maximum streaming bit rate in kbps, 0 for no limit.
+    #[arg(long)]
+    pub max_bit_rate: Option<u64>,
+    /// Music folder the user may access, repeat for several. Replaces the current folders.
+    #[arg(long = "folder", value_name = "MUSIC_FOLDER_ID")]
+    pub folders: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// List users and their roles.
+    List,
+    /// Show a user's settings.
+    Show { username: String },
+    /// Create a user, prompting for the password.
+    Create {
+        username: String,
+        #[command(flatten)]
+        settings: UserSettingsArgs,
+        /// Role to grant, repeat for several, e.g. `--role stream --role playlist`.
+        #[arg(long = "role")]
+        roles: Vec<Role>,
+    },
+    /// Change a user's settings and roles.
+    Update {
+        username: String,
+        #[command(flatten)]
+        settings: UserSettingsArgs,
+        /// Role to grant, repeat for several.
+        #[arg(long)]
+        grant: Vec<Role>,
+        /// Role to take away, repeat for several.
+        #[arg(long)]
+        revoke: Vec<Role>,
+        /// Do not ask for confirmation before taking roles away or replacing folders.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Delete a user.
+    Delete {
+        username: String,
+        /// Do not ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Set a user's password, prompting for the new one.
+    Password {
+        username: String,
+        /// Do not ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum JukeboxCommand {
+    /// Show whether the jukebox is playing, and what.
+    Status,
+    /// List the jukebox playlist.
+    List,
+    /// Replace the jukebox playlist with songs.
+    Set {
+        #[arg(required = true)]
+        songs: Vec<String>,
+    },
+    /// Add songs to the end of the jukebox playlist.
+    Add {
+        #[arg(required = true)]
+        songs: Vec<String>,
+    },
+    /// Remove the song at a position in the jukebox playlist, counting from 0.
+    Remove { index: u64 },
+    /// Remove all songs from the jukebox playlist.
+    Clear,
+    /// Shuffle the jukebox playlist.
+    Shuffle,
+    /// Start playing the jukebox playlist from the current song.
+    Start,
+    /// Stop the jukebox, keeping its playlist and position.
+    Stop,
+    /// Jump to the song at a position in the jukebox playlist, counting from 0.
+    Skip {
+        index: u64,
+        /// Seconds into the song to start at.
+        #[arg(long)]
+        offset: Option<u64>,
+    },
+    /// Set the volume, from 0.0 to 1.0.
+    Gain {
+        #[arg(value_parser = jukebox::parse_gain)]
+        gain: f32,
+    },
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use knuckles::player::Player;
+use knuckles::playlist::{match_entries, MatchedBy, PlaylistFile, PlaylistFormat};
+use knuckles::snapshot::Snapshot;
+use knuckles::types::PlaylistId;
+
+use super::PlaylistCommand;
+
+fn format_for(path: Option<&Path>, format: Option<PlaylistFormat>) -> Result<PlaylistFormat> {
+    match (format, path) {
+        (Some(format), _) => Ok(format),
+        (None, None) => Ok(PlaylistFormat::M3u8),
+        (None, Some(path)) => PlaylistFormat::from_path(path).with_context(|| {
+            format!(
+                "Could not tell the playlist format of {}, pass --format.",
+                path.display()
+            )
+        }),
+    }
+}
+
+pub async fn playlist(
+    player: &Player,
+    snapshot: &Snapshot,
+    command: &PlaylistCommand,
+) -> Result<()> {
+    let client = player.client();
+
+    match command {
+        PlaylistCommand::List => {
+            for playlist in client.playlists().await? {
+                println!(
+                    "{}  {} ({} songs)",
+                    playlist.id, playlist.name, playlist.song_count
+                );
+            }
+        }
+        PlaylistCommand::Export {
+            playlist,
+            output,
+            format,
+            prefix,
+        } => {
+            let format = format_for(output.as_deref(), *format)?;
+            let playlist = client
+                .playlist(&PlaylistId::unchecked(playlist.as_str()))
+                .await?;
+
+            let mut file = PlaylistFile::from_playlist(&playlist);
+
+            if let Some(prefix) = prefix {
+                for entry in &mut file.entries {
+                    if let Some(location) = &mut entry.location {
+                        location.insert_str(0, prefix);
+                    }
+                }
+            }
+
+            let text = file.write(format)?;
+
+            match output {
+                Some(output) => std::fs::write(output, text)?,
+                None => print!("{text}"),
+            }
+        }
+        PlaylistCommand::Import { file, name, format } => {
+            let format = format_for(Some(file), *format)?;
+            let text = std::fs::read_to_string(file)
+                .with_context(|| format!("Could not read {}.", file.display()))?;
+            let parsed = PlaylistFile::parse(format, &text)?;
+
+            let name = name
+                .clone()
+                .or(parsed.title)
+                .or_else(|| Some(file.file_stem()?.to_string_lossy().into_owned()))
+                .context("The playlist has no name, pass --name.")?;
+
+            let library = snapshot.all_songs()?;
+
+            if library.is_empty() {
+                eprintln!(
+                    "The library snapshot is empty, so songs are only matched by searching. \
+                     Run `library refresh` to also match by path and MusicBrainz ID."
+                );
+            }
+
+            let total = parsed.entries.len();
+            let matches = match_entries(client, &library, parsed.entries).await?;
+
+            for entry in &matches.unmatched {
+                eprintln!("No match for {entry}");
+            }
+
+            if matches.songs.is_empty() {
+                bail!("None of the {total} songs in {} matched.", file.display());
+            }
+
+            let searched = matches
+                .songs
+                .iter()
+                .filter(|(_, matched_by)| *matched_by == MatchedBy::Search)
+                .count();
+            let ids: Vec<_> = matches.songs.into_iter().map(|(id, _)| id).collect();
+
+            let playlist = client.create_playlist(&name, &ids).await?;
+
+            println!(
+                "Created playlist {} ({}) with {} of {total} songs, {searched} found by searching.",
+                playlist.playlist_data.name,
+                playlist.playlist_data.id,
+                ids.len()
+            );
+        }
+    }
+
+    Ok(())
+}
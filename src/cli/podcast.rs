@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+
+use knuckles::api_types::{PodcastEpisode, PodcastStatus};
+use knuckles::player::Player;
+use knuckles::types::{PodcastChannelId, PodcastEpisodeId, Strong};
+
+use super::PodcastCommand;
+
+pub async fn podcast(player: &mut Player, command: &PodcastCommand) -> Result<()> {
+    let client = player.client();
+
+    match command {
+        PodcastCommand::List { channel: None } => {
+            for channel in client.podcasts(Some(false), None).await? {
+                println!(
+                    "{}  {} ({:?})",
+                    channel.id,
+                    channel.title.as_deref().unwrap_or(&channel.url),
+                    channel.status
+                );
+            }
+        }
+        PodcastCommand::List {
+            channel: Some(channel),
+        } => {
+            let id = PodcastChannelId::unchecked(channel.as_str());
+
+            for channel in client.podcasts(Some(true), Some(&id)).await? {
+                print_episodes(&channel.episode.unwrap_or_default());
+            }
+        }
+        PodcastCommand::Newest { count } => {
+            print_episodes(&client.newest_podcasts(*count).await?);
+        }
+        PodcastCommand::Refresh => client.refresh_podcasts().await?,
+        PodcastCommand::Add { url } => client.create_podcast_channel(url).await?,
+        PodcastCommand::Remove { channel } => {
+            let id = PodcastChannelId::unchecked(channel.as_str());
+
+            client.delete_podcast_channel(&id).await?;
+        }
+        PodcastCommand::Download { episode } => {
+            let id = PodcastEpisodeId::unchecked(episode.as_str());
+
+            client.download_podcast_episode(&id).await?;
+        }
+        PodcastCommand::Delete { episode } => {
+            let id = PodcastEpisodeId::unchecked(episode.as_str());
+
+            client.delete_podcast_episode(&id).await?;
+        }
+        PodcastCommand::Play { episodes } => return play_episodes(player, episodes).await,
+    }
+
+    Ok(())
+}
+
+fn print_episodes(episodes: &[PodcastEpisode]) {
+    for episode in episodes {
+        let published = episode
+            .publish_date
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        println!(
+            "{}  {published:>10}  {} ({:?})",
+            episode.id, episode.title, episode.status
+        );
+    }
+}
+
+async fn play_episodes(player: &mut Player, ids: &[String]) -> Result<()> {
+    let mut available: Vec<PodcastEpisode> = player
+        .client()
+        .podcasts(Some(true), None)
+        .await?
+        .into_iter()
+        .flat_map(|channel| channel.episode.unwrap_or_default())
+        .collect();
+
+    let mut songs = Vec::new();
+
+    for id in ids {
+        let Some(index) = available
+            .iter()
+            .position(|episode| episode.id.get_ref() == id)
+        else {
+            bail!("No podcast episode with ID {id}.");
+        };
+
+        let episode = available.swap_remove(index);
+
+        match episode.song() {
+            Some(song) if episode.status == PodcastStatus::Completed => songs.push(song),
+            _ => bail!(
+                "Episode {} has not been downloaded by the server ({:?}).",
+                episode.title,
+                episode.status
+            ),
+        }
+    }
+
+    player.enqueue(songs);
+    player.play().await
+}
@@ -0,0 +1,52 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use anyhow::{bail, Result};
+
+pub fn prompt(label: &str) -> Result<String> {
+    print!("{label}: ");
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        bail!("Input ended while waiting for {label}.");
+    }
+
+    Ok(line.trim().to_owned())
+}
+
+pub fn prompt_until<T, E: std::fmt::Display>(
+    label: &str,
+    parse: impl Fn(String) -> Result<T, E>,
+) -> Result<T> {
+    loop {
+        match parse(prompt(label)?) {
+            Ok(value) => return Ok(value),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+pub fn prompt_secret(label: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        return prompt(label);
+    }
+
+    Ok(rpassword::prompt_password(format!("{label}: "))?)
+}
+
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+
+    loop {
+        match prompt(&format!("{question} [{hint}]"))?
+            .to_lowercase()
+            .as_str()
+        {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => eprintln!("Please answer y or n."),
+        }
+    }
+}
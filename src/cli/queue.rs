@@ -0,0 +1,53 @@
+use anyhow::Result;
+
+use knuckles::player::Player;
+
+use super::prompt::confirm;
+
+/// Offers to resume the play queue saved on the server, returning whether it was queued.
+pub async fn offer_resume(player: &mut Player) -> Result<bool> {
+    // Resuming is only an offer, so servers that cannot return the queue just play as usual.
+    let play_queue = match player.client().play_queue().await {
+        Ok(Some(play_queue)) => play_queue,
+        Ok(None) => return Ok(false),
+        Err(e) => {
+            eprintln!("Could not load the saved play queue: {e}");
+            return Ok(false);
+        }
+    };
+
+    let entries = play_queue.entry.as_deref().unwrap_or_default();
+
+    let current = play_queue
+        .current
+        .as_ref()
+        .and_then(|current| entries.iter().find(|song| &song.id == current));
+
+    let Some(current) = current else {
+        return Ok(false);
+    };
+
+    let position = play_queue.position.unwrap_or_default() / 1000;
+
+    println!(
+        "{} songs were saved by {} at {}.",
+        entries.len(),
+        play_queue.changed_by,
+        play_queue.changed.format("%Y-%m-%d %H:%M")
+    );
+
+    let question = format!(
+        "Resume from {} at {}:{:02}?",
+        current.title,
+        position / 60,
+        position % 60
+    );
+
+    if !confirm(&question, true)? {
+        return Ok(false);
+    }
+
+    player.resume(play_queue);
+
+    Ok(true)
+}
@@ -0,0 +1,19 @@
+use anyhow::Result;
+
+use knuckles::client::RandomSongsFilter;
+use knuckles::player::Player;
+use knuckles::radio::Radio;
+use knuckles::types::ArtistId;
+
+pub async fn play_radio(
+    player: &mut Player,
+    artist: Option<&str>,
+    filter: RandomSongsFilter,
+) -> Result<()> {
+    let artist = artist.map(ArtistId::unchecked);
+
+    println!("Starting radio, press Ctrl-C to stop.");
+
+    player.set_radio(Some(Radio::new(artist, filter)));
+    player.play().await
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+
+use knuckles::api_types::Share;
+use knuckles::player::Player;
+use knuckles::types::ShareId;
+
+use super::ShareCommand;
+
+fn print_share(share: &Share) {
+    let expires = share
+        .expires
+        .map(|expires| format!(", expires {}", expires.format("%Y-%m-%d %H:%M")))
+        .unwrap_or_default();
+
+    println!(
+        "{}  {}  {}{expires}",
+        share.id,
+        share.url,
+        share.description.as_deref().unwrap_or_default()
+    );
+}
+
+pub async fn share(player: &Player, command: &ShareCommand) -> Result<()> {
+    let client = player.client();
+
+    match command {
+        ShareCommand::List => {
+            for share in client.shares().await? {
+                print_share(&share);
+            }
+        }
+        ShareCommand::Create {
+            ids,
+            description,
+            expires,
+        } => {
+            let share = client
+                .create_share(ids, description.as_deref(), *expires)
+                .await?;
+
+            println!("{}", share.url);
+        }
+        ShareCommand::Update {
+            share,
+            description,
+            expires,
+        } => {
+            let id = ShareId::unchecked(share.as_str());
+
+            client
+                .update_share(&id, description.as_deref(), *expires)
+                .await?
+        }
+        ShareCommand::Delete { share } => {
+            client
+                .delete_share(&ShareId::unchecked(share.as_str()))
+                .await?
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+
+use knuckles::api_types::AlbumID3WithSongs;
+use knuckles::client::SubsonicClient;
+use knuckles::player::Player;
+use knuckles::smart::{parse_seconds, save_to_server, SmartPlaylist};
+use knuckles::snapshot::fetch_albums;
+
+use super::SmartCommand;
+
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    parse_seconds(value)
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Expected an interval such as 30m, 6h or 1d, got {value}."))
+}
+
+fn find<'a>(
+    playlists: &'a BTreeMap<String, SmartPlaylist>,
+    name: &str,
+) -> Result<&'a SmartPlaylist> {
+    match playlists.get(name) {
+        Some(playlist) => Ok(playlist),
+        None if playlists.is_empty() => bail!(
+            "No smart playlist named {name}. Add a [smart_playlists.{name}] section with its rules."
+        ),
+        None => bail!(
+            "No smart playlist named {name}. Available smart playlists: {}.",
+            playlists.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+// Play counts and stars change all the time, so rules always see the library as it is now.
+async fn fetch_library(client: &SubsonicClient) -> Result<Vec<AlbumID3WithSongs>> {
+    eprintln!("Fetching the library to apply the rules.");
+
+    fetch_albums(client).await
+}
+
+async fn save(client: &SubsonicClient, playlists: &[(&str, &SmartPlaylist)]) -> Result<()> {
+    let albums = fetch_library(client).await?;
+
+    for (key, playlist) in playlists {
+        let name = playlist.name.as_deref().unwrap_or(key);
+        let ids: Vec<_> = playlist
+            .songs(&albums, Utc::now(), &mut rand::thread_rng())
+            .into_iter()
+            .map(|song| song.id)
+            .collect();
+
+        let id = save_to_server(client, name, playlist, &ids).await?;
+
+        println!("Saved {} songs to playlist {name} ({id}).", ids.len());
+    }
+
+    Ok(())
+}
+
+pub async fn smart(
+    player: &mut Player,
+    playlists: &BTreeMap<String, SmartPlaylist>,
+    command: &SmartCommand,
+) -> Result<()> {
+    match command {
+        SmartCommand::List => {
+            for (name, playlist) in playlists {
+                println!("{name}  {}", playlist.description());
+            }
+        }
+        SmartCommand::Show { name } => {
+            let playlist = find(playlists, name)?;
+            let albums = fetch_library(player.client()).await?;
+
+            for song in playlist.songs(&albums, Utc::now(), &mut rand::thread_rng()) {
+                println!(
+                    "{}  {} - {} - {}",
+                    song.id,
+                    song.artist.as_deref().unwrap_or("Unknown"),
+                    song.album.as_deref().unwrap_or("Unknown"),
+                    song.title
+                );
+            }
+        }
+        SmartCommand::Play { name } => {
+            let playlist = find(playlists, name)?;
+            let albums = fetch_library(player.client()).await?;
+            let songs = playlist.songs(&albums, Utc::now(), &mut rand::thread_rng());
+
+            if songs.is_empty() {
+                bail!("No songs match the rules of smart playlist {name}.");
+            }
+
+            player.enqueue(songs);
+            player.play().await?;
+        }
+        SmartCommand::Save { names, every } => {
+            let selected = if names.is_empty() {
+                playlists
+                    .iter()
+                    .map(|(name, playlist)| (name.as_str(), playlist))
+                    .collect()
+            } else {
+                names
+                    .iter()
+                    .map(|name| Ok((name.as_str(), find(playlists, name)?)))
+                    .collect::<Result<Vec<_>>>()?
+            };
+
+            if selected.is_empty() {
+                bail!("No smart playlists configured. Add a [smart_playlists.<name>] section with its rules.");
+            }
+
+            loop {
+                let result = save(player.client(), &selected).await;
+
+                let Some(every) = every else {
+                    return result;
+                };
+
+                // A server that is briefly unreachable should not stop the schedule.
+                if let Err(e) = result {
+                    eprintln!("Could not save smart playlists, trying again later: {e}");
+                }
+
+                tokio::time::sleep(*every).await;
+            }
+        }
+    }
+
+    Ok(())
+}
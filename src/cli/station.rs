@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+
+use knuckles::player::Player;
+use knuckles::types::{RadioStationId, Strong};
+
+use super::StationCommand;
+
+pub async fn station(player: &Player, command: &StationCommand) -> Result<()> {
+    let client = player.client();
+
+    match command {
+        StationCommand::List => {
+            for station in client.internet_radio_stations().await? {
+                println!("{}  {} ({})", station.id, station.name, station.stream_url);
+            }
+        }
+        StationCommand::Add {
+            name,
+            url,
+            homepage,
+        } => {
+            client
+                .create_internet_radio_station(url, name, homepage.as_deref())
+                .await?
+        }
+        StationCommand::Update {
+            station,
+            name,
+            url,
+            homepage,
+        } => {
+            let id = RadioStationId::unchecked(station.as_str());
+
+            client
+                .update_internet_radio_station(&id, url, name, homepage.as_deref())
+                .await?
+        }
+        StationCommand::Remove { station } => {
+            let id = RadioStationId::unchecked(station.as_str());
+
+            client.delete_internet_radio_station(&id).await?
+        }
+        StationCommand::Play { station } => {
+            let found = client
+                .internet_radio_stations()
+                .await?
+                .into_iter()
+                .find(|candidate| {
+                    candidate.id.get_ref() == station
+                        || candidate.name.eq_ignore_ascii_case(station)
+                })
+                .with_context(|| format!("No internet radio station named {station}."))?;
+
+            player.play_station(&found).await?
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use knuckles::history::History;
+use knuckles::stats::{Period, Report, ReportFormat, Tally};
+
+pub struct Range {
+    pub year: Option<i32>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+fn start_of_year(year: i32) -> Result<DateTime<Utc>> {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+        .with_context(|| format!("Invalid year {year}."))
+}
+
+fn format_time(seconds: u64) -> String {
+    let minutes = seconds / 60;
+
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {:02}m", minutes % 60),
+    }
+}
+
+fn print_tallies(heading: &str, tallies: &[Tally]) {
+    if tallies.is_empty() {
+        return;
+    }
+
+    println!("\n{heading}");
+
+    for (rank, tally) in tallies.iter().enumerate() {
+        println!(
+            "{:>3}. {}  {} plays, {}",
+            rank + 1,
+            tally.name,
+            tally.plays,
+            format_time(tally.seconds)
+        );
+    }
+}
+
+fn print_report(report: &Report, period: Period) {
+    println!(
+        "{} plays, {} stopped early ({:.0}%)",
+        report.total.plays,
+        report.total.stopped_early,
+        report.stopped_early_rate * 100.0
+    );
+    println!("Listening time: {}", format_time(report.total.seconds));
+
+    print_tallies("Top artists", &report.top_artists);
+    print_tallies("Top albums", &report.top_albums);
+    print_tallies("Top genres", &report.top_genres);
+
+    if !report.periods.is_empty() {
+        let heading = match period {
+            Period::Day => "Listening time per day",
+            Period::Week => "Listening time per week",
+            Period::Month => "Listening time per month",
+            Period::Year => "Listening time per year",
+        };
+
+        println!("\n{heading}");
+
+        for tally in &report.periods {
+            println!("  {}  {}", tally.name, format_time(tally.seconds));
+        }
+    }
+}
+
+pub fn stats(
+    history: &History,
+    range: &Range,
+    period: Period,
+    limit: usize,
+    format: Option<ReportFormat>,
+) -> Result<()> {
+    let (since, until) = match range.year {
+        Some(year) => (Some(start_of_year(year)?), Some(start_of_year(year + 1)?)),
+        None => (range.since, range.until),
+    };
+
+    let plays = history.plays(since, until)?;
+    let report = Report::new(&plays, period, limit);
+
+    match format {
+        Some(format) => print!("{}", report.write(format)?),
+        None if plays.is_empty() => {
+            println!("No plays recorded, only songs played on this computer are counted.")
+        }
+        None => print_report(&report, period),
+    }
+
+    Ok(())
+}
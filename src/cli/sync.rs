@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use knuckles::client::{AlbumListType, SubsonicClient};
+use knuckles::download::{FileNameTemplate, Outcome};
+use knuckles::snapshot::Snapshot;
+use knuckles::sync::{Progress, Selector};
+use knuckles::types::PlaylistId;
+
+use super::SyncAlbumList;
+
+pub fn selectors(
+    playlists: &[String],
+    starred: bool,
+    album_lists: &[SyncAlbumList],
+    album_list_size: u64,
+) -> Vec<Selector> {
+    let mut selectors: Vec<_> = playlists
+        .iter()
+        .map(|id| Selector::Playlist(PlaylistId::unchecked(id.as_str())))
+        .collect();
+
+    if starred {
+        selectors.push(Selector::Starred);
+    }
+
+    selectors.extend(album_lists.iter().map(|album_list| {
+        let list_type = match album_list {
+            SyncAlbumList::Newest => AlbumListType::Newest,
+            SyncAlbumList::Recent => AlbumListType::Recent,
+            SyncAlbumList::Frequent => AlbumListType::Frequent,
+            SyncAlbumList::Highest => AlbumListType::Highest,
+            SyncAlbumList::Random => AlbumListType::Random,
+        };
+
+        Selector::AlbumList {
+            list_type,
+            size: album_list_size,
+        }
+    }));
+
+    selectors
+}
+
+pub async fn sync(
+    client: &SubsonicClient,
+    snapshot: &mut Snapshot,
+    directory: &Path,
+    selectors: &[Selector],
+    template: &str,
+) -> Result<()> {
+    let template = FileNameTemplate::new(template).context("Invalid download template.")?;
+
+    let mut deleted = Vec::new();
+    let mut synced = Vec::new();
+
+    let result =
+        knuckles::sync::sync(
+            client,
+            directory,
+            selectors,
+            &template,
+            |progress| match progress {
+                Progress::Deleted(path) => {
+                    println!("Deleted {}", path.display());
+                    deleted.push(path.to_owned());
+                }
+                Progress::Synced(path, song, outcome) => {
+                    match outcome {
+                        Outcome::Downloaded => println!("Downloaded {}", path.display()),
+                        Outcome::Resumed => println!("Resumed {}", path.display()),
+                        Outcome::Skipped => {}
+                    }
+
+                    synced.push((path.to_owned(), song.clone()));
+                }
+                Progress::Failed(path, e) => {
+                    eprintln!("Could not download {}: {e:#}", path.display())
+                }
+            },
+        )
+        .await;
+
+    // Deleted files no longer play offline, while songs that did sync do, even if
+    // others failed. A song moved to another path is forgotten first, then remembered.
+    if !deleted.is_empty() {
+        let directory = directory.canonicalize()?;
+
+        for path in &deleted {
+            snapshot.remove_local_file(&directory.join(path))?;
+        }
+    }
+
+    super::download::remember_local_files(snapshot, directory, &synced)?;
+
+    result
+}
@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+
+use knuckles::api_types::{Role, User};
+use knuckles::client::UserSettings;
+use knuckles::player::Player;
+use knuckles::types::{MusicFolderId, Password};
+
+use super::prompt::{confirm, prompt_secret};
+use super::{UserCommand, UserSettingsArgs};
+
+fn settings(args: &UserSettingsArgs, roles: Vec<(Role, bool)>) -> UserSettings {
+    let music_folder_ids = (!args.folders.is_empty()).then(|| {
+        args.folders
+            .iter()
+            .map(|id| MusicFolderId::unchecked(id.as_str()))
+            .collect()
+    });
+
+    UserSettings {
+        email: args.email.clone(),
+        max_bit_rate: args.max_bit_rate,
+        roles,
+        music_folder_ids,
+    }
+}
+
+fn new_password(username: &str) -> Result<Password> {
+    let password = prompt_secret(&format!("New password for {username}"))?;
+
+    if password.is_empty() {
+        bail!("The password must not be empty.");
+    }
+
+    if prompt_secret("Repeat the password")? != password {
+        bail!("The passwords do not match.");
+    }
+
+    Ok(Password(password))
+}
+
+fn print_user(user: &User) {
+    let roles: Vec<_> = user.roles().iter().map(Role::to_string).collect();
+
+    println!(
+        "{}  {}",
+        user.username,
+        user.email.as_deref().unwrap_or_default()
+    );
+    println!("  roles: {}", roles.join(", "));
+
+    if let Some(max_bit_rate) = user.max_bit_rate.filter(|rate| *rate > 0) {
+        println!("  max bit rate: {max_bit_rate} kbps");
+    }
+
+    if let Some(folders) = &user.folder {
+        let folders: Vec<_> = folders.iter().map(MusicFolderId::to_string).collect();
+
+        println!("  folders: {}", folders.join(", "));
+    }
+}
+
+pub async fn user(player: &Player, command: &UserCommand) -> Result<()> {
+    let client = player.client();
+
+    match command {
+        UserCommand::List => {
+            for user in client.users().await? {
+                print_user(&user);
+            }
+        }
+        UserCommand::Show { username } => print_user(&client.user(username).await?),
+        UserCommand::Create {
+            username,
+            settings: args,
+            roles,
+        } => {
+            let roles = roles.iter().map(|role| (*role, true)).collect();
+            let password = new_password(username)?;
+
+            client
+                .create_user(username, &password, &settings(args, roles))
+                .await?;
+        }
+        UserCommand::Update {
+            username,
+            settings: args,
+            grant,
+            revoke,
+            yes,
+        } => {
+            let revoked: Vec<_> = revoke.iter().map(Role::to_string).collect();
+            let folders = args.folders.join(", ");
+
+            let question = match (revoked.is_empty(), args.folders.is_empty()) {
+                (true, true) => None,
+                (false, true) => Some(format!("Revoke {} from {username}?", revoked.join(", "))),
+                (true, false) => Some(format!("Limit {username} to music folders {folders}?")),
+                (false, false) => Some(format!(
+                    "Revoke {} from {username} and limit them to music folders {folders}?",
+                    revoked.join(", ")
+                )),
+            };
+
+            let confirmed = match question {
+                Some(question) if !*yes => confirm(&question, false)?,
+                _ => true,
+            };
+
+            if confirmed {
+                let roles = grant
+                    .iter()
+                    .map(|role| (*role, true))
+                    .chain(revoke.iter().map(|role| (*role, false)))
+                    .collect();
+
+                client
+                    .update_user(username, None, &settings(args, roles))
+                    .await?;
+            }
+        }
+        UserCommand::Delete { username, yes } => {
+            if *yes || confirm(&format!("Delete user {username}?"), false)? {
+                client.delete_user(username).await?;
+            }
+        }
+        UserCommand::Password { username, yes } => {
+            if *yes || confirm(&format!("Change the password of {username}?"), false)? {
+                let password = new_password(username)?;
+
+                client.change_password(username, &password).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
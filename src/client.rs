@@ -1,12 +1,27 @@
 use core::fmt;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use reqwest::Url;
 
-use crate::api_types::{AlbumID3WithSongs, AlbumListItem, OuterSubsonicResponse, ScanStatus, SubsonicResponse};
-use crate::error::OnMissing;
+use chrono::{DateTime, Utc};
+
+use crate::api_types::{
+    AlbumID3, AlbumID3WithSongs, AlbumListItem, ArtistID3WithAlbums, Bookmark, ClassicLyrics,
+    Genre, Indexes, InternetRadioStation, JukeboxStatus, MusicDirectory, MusicFolder,
+    OuterSubsonicResponse, PlayQueue, Playlist, PlaylistWithSongs, PodcastChannel, PodcastEpisode,
+    Role, ScanStatus, Share, Song, Starred2, StructuredLyrics, SubsonicResponse, User,
+};
+use crate::error::{check_at_most, OnMissing};
 use crate::token::TokenInfo;
-use crate::types::{AlbumId, MusicFolderId, ServerUrl, SongId, Strong, Username};
+use crate::types::{
+    AlbumId, ArtistId, DirectoryId, MusicFolderId, Password, PlaylistId, PodcastChannelId,
+    PodcastEpisodeId, RadioStationId, ServerUrl, ShareId, SongId, Strong, Username,
+};
+
+pub const MAX_ALBUM_LIST_SIZE: u64 = 500;
+pub const MAX_SONGS_BY_GENRE_COUNT: u64 = 500;
+pub const MAX_RANDOM_SONGS_SIZE: u64 = 500;
+pub const MAX_SEARCH_COUNT: u64 = 500;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct SubsonicClient {
@@ -20,6 +35,7 @@ trait WriteToUrl {
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub enum AlbumListType {
     Random,
     Newest,
@@ -29,7 +45,7 @@ pub enum AlbumListType {
     AlphabeticalByName,
     AlphabeticalByArtist,
     Starred,
-    ByYear { from_year: String, to_year: String },
+    ByYear { from_year: u64, to_year: u64 },
     ByGenre(String),
 }
 
@@ -59,8 +75,8 @@ impl WriteToUrl for AlbumListType {
 
         match &self {
             AlbumListType::ByYear { from_year, to_year } => {
-                qp.append_pair("fromYear", from_year);
-                qp.append_pair("toYear", to_year);
+                qp.append_pair("fromYear", &from_year.to_string());
+                qp.append_pair("toYear", &to_year.to_string());
             }
             AlbumListType::ByGenre(genre) => {
                 qp.append_pair("genre", genre);
@@ -72,14 +88,195 @@ impl WriteToUrl for AlbumListType {
     }
 }
 
+pub enum JukeboxAction {
+    Get,
+    Status,
+    Set(Vec<SongId>),
+    Start,
+    Stop,
+    /// Jumps to the song at `index` in the jukebox playlist, `offset` seconds in.
+    Skip {
+        index: u64,
+        offset: Option<u64>,
+    },
+    Add(Vec<SongId>),
+    Clear,
+    Remove(u64),
+    Shuffle,
+    /// Volume from 0.0 to 1.0.
+    SetGain(f32),
+}
+
+impl fmt::Display for JukeboxAction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use JukeboxAction::*;
+        let s = match &self {
+            Get => "get",
+            Status => "status",
+            Set(_) => "set",
+            Start => "start",
+            Stop => "stop",
+            Skip { .. } => "skip",
+            Add(_) => "add",
+            Clear => "clear",
+            Remove(_) => "remove",
+            Shuffle => "shuffle",
+            SetGain(_) => "setGain",
+        };
+
+        fmt.write_str(s)
+    }
+}
+
+impl WriteToUrl for JukeboxAction {
+    fn write_to_url(&self, url: &mut Url) {
+        let mut qp = url.query_pairs_mut();
+
+        qp.append_pair("action", &self.to_string());
+
+        match &self {
+            JukeboxAction::Set(ids) | JukeboxAction::Add(ids) => {
+                for id in ids {
+                    qp.append_pair("id", id.get_ref());
+                }
+            }
+            JukeboxAction::Skip { index, offset } => {
+                qp.append_pair("index", &index.to_string());
+
+                if let Some(offset) = offset {
+                    qp.append_pair("offset", &offset.to_string());
+                }
+            }
+            JukeboxAction::Remove(index) => {
+                qp.append_pair("index", &index.to_string());
+            }
+            JukeboxAction::SetGain(gain) => {
+                qp.append_pair("gain", &gain.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RandomSongsFilter {
+    pub genre: Option<String>,
+    pub from_year: Option<u64>,
+    pub to_year: Option<u64>,
+    pub music_folder_id: Option<MusicFolderId>,
+}
+
+impl WriteToUrl for RandomSongsFilter {
+    fn write_to_url(&self, url: &mut Url) {
+        let mut qp = url.query_pairs_mut();
+
+        if let Some(genre) = &self.genre {
+            qp.append_pair("genre", genre);
+        }
+
+        if let Some(from_year) = self.from_year {
+            qp.append_pair("fromYear", &from_year.to_string());
+        }
+
+        if let Some(to_year) = self.to_year {
+            qp.append_pair("toYear", &to_year.to_string());
+        }
+
+        if let Some(music_folder_id) = &self.music_folder_id {
+            qp.append_pair("musicFolderId", music_folder_id.get_ref());
+        }
+    }
+}
+
+fn write_station(url: &mut Url, stream_url: &str, name: &str, home_page_url: Option<&str>) {
+    let mut qp = url.query_pairs_mut();
+
+    qp.append_pair("streamUrl", stream_url);
+    qp.append_pair("name", name);
+
+    if let Some(home_page_url) = home_page_url {
+        qp.append_pair("homepageUrl", home_page_url);
+    }
+}
+
+fn write_share(url: &mut Url, description: Option<&str>, expires: Option<DateTime<Utc>>) {
+    let mut qp = url.query_pairs_mut();
+
+    if let Some(description) = description {
+        qp.append_pair("description", description);
+    }
+
+    if let Some(expires) = expires {
+        qp.append_pair("expires", &expires.timestamp_millis().to_string());
+    }
+}
+
+/// Settings for createUser and updateUser, where unset values keep the server's default
+/// or the user's current value.
+#[derive(Default)]
+pub struct UserSettings {
+    pub email: Option<String>,
+    pub max_bit_rate: Option<u64>,
+    pub roles: Vec<(Role, bool)>,
+    pub music_folder_ids: Option<Vec<MusicFolderId>>,
+}
+
+impl WriteToUrl for UserSettings {
+    fn write_to_url(&self, url: &mut Url) {
+        let mut qp = url.query_pairs_mut();
+
+        if let Some(email) = &self.email {
+            qp.append_pair("email", email);
+        }
+
+        if let Some(max_bit_rate) = self.max_bit_rate {
+            qp.append_pair("maxBitRate", &max_bit_rate.to_string());
+        }
+
+        for (role, enabled) in &self.roles {
+            qp.append_pair(role.parameter(), &enabled.to_string());
+        }
+
+        for id in self.music_folder_ids.iter().flatten() {
+            qp.append_pair("musicFolderId", id.get_ref());
+        }
+    }
+}
+
+// User management takes the password itself rather than a token, hex encoded behind `enc:`
+// so special characters survive the query string.
+fn encode_password(password: &Password) -> String {
+    let hex: String = password
+        .get_ref()
+        .bytes()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    format!("enc:{hex}")
+}
+
 async fn raw_subsonic_request(url: Url) -> Result<OuterSubsonicResponse> {
-    let json = reqwest::get(url).await?.text().await?;
+    // Request URLs carry the authentication token, so keep them out of errors.
+    let json = reqwest::get(url)
+        .await
+        .map_err(reqwest::Error::without_url)?
+        .text()
+        .await?;
 
     Ok(serde_json::from_str(&json)?)
 }
 
+fn check_response(response: OuterSubsonicResponse) -> Result<SubsonicResponse> {
+    let mut response = response.subsonic_response;
+
+    match response.error.take() {
+        Some(error) => Err(error.into()),
+        None => Ok(response),
+    }
+}
+
 async fn subsonic_request(url: Url) -> Result<SubsonicResponse> {
-    Ok(raw_subsonic_request(url).await?.subsonic_response)
+    check_response(raw_subsonic_request(url).await?)
 }
 
 impl SubsonicClient {
@@ -93,9 +290,17 @@ impl SubsonicClient {
             ("c", "knuckles"),
         ];
 
-        let mut url = Url::parse_with_params(self.url.get_ref(), &params)?;
+        let mut server_url = Url::parse(self.url.get_ref())?;
 
-        url.set_path(&format!("rest/{path}"));
+        // Join relative to the configured path, so servers hosted under a sub-path work.
+        if !server_url.path().ends_with('/') {
+            let base_path = format!("{}/", server_url.path());
+            server_url.set_path(&base_path);
+        }
+
+        let mut url = server_url.join(&format!("rest/{path}"))?;
+
+        url.query_pairs_mut().extend_pairs(params);
 
         Ok(url)
     }
@@ -104,18 +309,20 @@ impl SubsonicClient {
         subsonic_request(self.base_url("ping")?).await
     }
 
-    pub async fn albums(
+    fn album_list_url(
         &self,
+        endpoint: &str,
         list_type: AlbumListType,
         size: Option<u64>,
         offset: Option<u64>,
         music_folder_id: Option<MusicFolderId>,
-    ) -> Result<Vec<AlbumListItem>> {
-        let mut url = self.base_url("getAlbumList")?;
+    ) -> Result<Url> {
+        let mut url = self.base_url(endpoint)?;
 
         list_type.write_to_url(&mut url);
 
         if let Some(size) = size {
+            let size = check_at_most("size", size, MAX_ALBUM_LIST_SIZE)?;
             let mut qp = url.query_pairs_mut();
             qp.append_pair("size", &size.to_string());
         }
@@ -127,9 +334,21 @@ impl SubsonicClient {
 
         if let Some(music_folder_id) = music_folder_id {
             let mut qp = url.query_pairs_mut();
-            qp.append_pair("music_folder_id", &music_folder_id.get());
+            qp.append_pair("musicFolderId", &music_folder_id.get());
         }
 
+        Ok(url)
+    }
+
+    pub async fn albums(
+        &self,
+        list_type: AlbumListType,
+        size: Option<u64>,
+        offset: Option<u64>,
+        music_folder_id: Option<MusicFolderId>,
+    ) -> Result<Vec<AlbumListItem>> {
+        let url = self.album_list_url("getAlbumList", list_type, size, offset, music_folder_id)?;
+
         let albums = subsonic_request(url)
             .await?
             .album_list
@@ -140,6 +359,26 @@ impl SubsonicClient {
         Ok(albums)
     }
 
+    /// Like `albums`, but organized by ID3 tags, so the IDs work with `album`.
+    pub async fn albums2(
+        &self,
+        list_type: AlbumListType,
+        size: Option<u64>,
+        offset: Option<u64>,
+        music_folder_id: Option<MusicFolderId>,
+    ) -> Result<Vec<AlbumID3>> {
+        let url = self.album_list_url("getAlbumList2", list_type, size, offset, music_folder_id)?;
+
+        let albums = subsonic_request(url)
+            .await?
+            .album_list2
+            .on_missing("album_list2")?
+            .album
+            .unwrap_or_else(Vec::new);
+
+        Ok(albums)
+    }
+
     pub async fn album(&self, id: &AlbumId) -> Result<AlbumID3WithSongs> {
         let mut url = self.base_url("getAlbum")?;
 
@@ -150,6 +389,144 @@ impl SubsonicClient {
         Ok(albums)
     }
 
+    pub async fn artist(&self, id: &ArtistId) -> Result<ArtistID3WithAlbums> {
+        let mut url = self.base_url("getArtist")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        let artist = subsonic_request(url).await?.artist.on_missing("artist")?;
+
+        Ok(artist)
+    }
+
+    pub async fn playlist(&self, id: &PlaylistId) -> Result<PlaylistWithSongs> {
+        let mut url = self.base_url("getPlaylist")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        let playlist = subsonic_request(url)
+            .await?
+            .playlist
+            .on_missing("playlist")?;
+
+        Ok(playlist)
+    }
+
+    pub async fn starred(&self) -> Result<Starred2> {
+        let starred = subsonic_request(self.base_url("getStarred2")?)
+            .await?
+            .starred2
+            .on_missing("starred2")?;
+
+        Ok(starred)
+    }
+
+    pub async fn playlists(&self) -> Result<Vec<Playlist>> {
+        let playlists = subsonic_request(self.base_url("getPlaylists")?)
+            .await?
+            .playlists
+            .on_missing("playlists")?
+            .playlist
+            .unwrap_or_else(Vec::new);
+
+        Ok(playlists)
+    }
+
+    pub async fn create_playlist(&self, name: &str, songs: &[SongId]) -> Result<PlaylistWithSongs> {
+        let mut url = self.base_url("createPlaylist")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("name", name);
+
+            for id in songs {
+                qp.append_pair("songId", id.get_ref());
+            }
+        }
+
+        let playlist = subsonic_request(url)
+            .await?
+            .playlist
+            .on_missing("playlist")?;
+
+        Ok(playlist)
+    }
+
+    /// Replaces all songs of a playlist, with createPlaylist as servers accept a playlist ID
+    /// there instead of a name.
+    pub async fn replace_playlist(&self, id: &PlaylistId, songs: &[SongId]) -> Result<()> {
+        let mut url = self.base_url("createPlaylist")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("playlistId", id.get_ref());
+
+            for id in songs {
+                qp.append_pair("songId", id.get_ref());
+            }
+        }
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_playlist(
+        &self,
+        id: &PlaylistId,
+        name: Option<&str>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let mut url = self.base_url("updatePlaylist")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("playlistId", id.get_ref());
+
+            if let Some(name) = name {
+                qp.append_pair("name", name);
+            }
+
+            if let Some(comment) = comment {
+                qp.append_pair("comment", comment);
+            }
+        }
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    /// Searches song titles, artists and albums with search3, leaving out artists and albums.
+    pub async fn search_songs(&self, query: &str, count: Option<u64>) -> Result<Vec<Song>> {
+        let mut url = self.base_url("search3")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("query", query);
+            qp.append_pair("artistCount", "0");
+            qp.append_pair("albumCount", "0");
+
+            if let Some(count) = count {
+                let count = check_at_most("songCount", count, MAX_SEARCH_COUNT)?;
+                qp.append_pair("songCount", &count.to_string());
+            }
+        }
+
+        let songs = subsonic_request(url)
+            .await?
+            .search_result3
+            .on_missing("search_result3")?
+            .song
+            .unwrap_or_else(Vec::new);
+
+        Ok(songs)
+    }
+
     pub async fn start_scan(&self) -> Result<ScanStatus> {
         let url = self.base_url("startScan")?;
 
@@ -166,47 +543,859 @@ impl SubsonicClient {
         Ok(scan_status)
     }
 
-    pub async fn stream(
+    pub async fn music_folders(&self) -> Result<Vec<MusicFolder>> {
+        let url = self.base_url("getMusicFolders")?;
+
+        let music_folders = subsonic_request(url)
+            .await?
+            .music_folders
+            .on_missing("music_folders")?
+            .music_folder
+            .unwrap_or_else(Vec::new);
+
+        Ok(music_folders)
+    }
+
+    pub async fn indexes(
         &self,
-        id: &SongId,
-        estimate_content_length: Option<bool>,
-    ) -> Result<reqwest::Response> {
-        let mut url = self.base_url("stream")?;
+        music_folder_id: Option<MusicFolderId>,
+        if_modified_since: Option<DateTime<Utc>>,
+    ) -> Result<Indexes> {
+        let mut url = self.base_url("getIndexes")?;
 
-        url.query_pairs_mut().append_pair("id", id.get_ref());
+        if let Some(music_folder_id) = music_folder_id {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("musicFolderId", &music_folder_id.get());
+        }
 
-        if let Some(estimate_content_length) = estimate_content_length {
+        if let Some(if_modified_since) = if_modified_since {
             let mut qp = url.query_pairs_mut();
             qp.append_pair(
-                "estimateContentLength",
-                &estimate_content_length.to_string(),
+                "ifModifiedSince",
+                &if_modified_since.timestamp_millis().to_string(),
             );
         }
 
-        Ok(reqwest::get(url).await?)
+        let indexes = subsonic_request(url).await?.indexes.on_missing("indexes")?;
+
+        Ok(indexes)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::types::{PasswordHash, Salt};
+    pub async fn music_directory(&self, id: &DirectoryId) -> Result<MusicDirectory> {
+        let mut url = self.base_url("getMusicDirectory")?;
 
-    use super::*;
+        url.query_pairs_mut().append_pair("id", id.get_ref());
 
-    #[test]
-    fn test_base_url() -> Result<()> {
-        let client = SubsonicClient {
-            url: ServerUrl::unchecked("https://subsonic.example.com"),
-            username: Username::unchecked("user"),
-            token_info: TokenInfo {
-                hash: PasswordHash::unchecked("a1b2c3"),
-                salt: Salt::unchecked("abcde"),
-            },
-        };
+        let directory = subsonic_request(url)
+            .await?
+            .directory
+            .on_missing("directory")?;
 
-        let base_url = client.base_url("ping")?;
+        Ok(directory)
+    }
 
-        assert_eq!(base_url, Url::parse("https://subsonic.example.com/rest/ping?f=json&u=user&t=a1b2c3&s=abcde&v=1.16.1&c=knuckles")?);
+    pub async fn genres(&self) -> Result<Vec<Genre>> {
+        let url = self.base_url("getGenres")?;
+
+        let genres = subsonic_request(url)
+            .await?
+            .genres
+            .on_missing("genres")?
+            .genre
+            .unwrap_or_else(Vec::new);
+
+        Ok(genres)
+    }
+
+    pub async fn songs_by_genre(
+        &self,
+        genre: &str,
+        count: Option<u64>,
+        offset: Option<u64>,
+        music_folder_id: Option<MusicFolderId>,
+    ) -> Result<Vec<Song>> {
+        let mut url = self.base_url("getSongsByGenre")?;
+
+        url.query_pairs_mut().append_pair("genre", genre);
+
+        if let Some(count) = count {
+            let count = check_at_most("count", count, MAX_SONGS_BY_GENRE_COUNT)?;
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("count", &count.to_string());
+        }
+
+        if let Some(offset) = offset {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("offset", &offset.to_string());
+        }
+
+        if let Some(music_folder_id) = music_folder_id {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("musicFolderId", &music_folder_id.get());
+        }
+
+        let songs = subsonic_request(url)
+            .await?
+            .songs_by_genre
+            .on_missing("songs_by_genre")?
+            .song
+            .unwrap_or_else(Vec::new);
+
+        Ok(songs)
+    }
+
+    pub async fn random_songs(
+        &self,
+        size: Option<u64>,
+        filter: &RandomSongsFilter,
+    ) -> Result<Vec<Song>> {
+        let mut url = self.base_url("getRandomSongs")?;
+
+        if let Some(size) = size {
+            let size = check_at_most("size", size, MAX_RANDOM_SONGS_SIZE)?;
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("size", &size.to_string());
+        }
+
+        filter.write_to_url(&mut url);
+
+        let songs = subsonic_request(url)
+            .await?
+            .random_songs
+            .on_missing("random_songs")?
+            .song
+            .unwrap_or_else(Vec::new);
+
+        Ok(songs)
+    }
+
+    pub async fn similar_songs2(&self, id: &ArtistId, count: Option<u64>) -> Result<Vec<Song>> {
+        let mut url = self.base_url("getSimilarSongs2")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        if let Some(count) = count {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("count", &count.to_string());
+        }
+
+        let songs = subsonic_request(url)
+            .await?
+            .similar_songs2
+            .on_missing("similar_songs2")?
+            .song
+            .unwrap_or_else(Vec::new);
+
+        Ok(songs)
+    }
+
+    pub async fn top_songs(&self, artist: &str, count: Option<u64>) -> Result<Vec<Song>> {
+        let mut url = self.base_url("getTopSongs")?;
+
+        url.query_pairs_mut().append_pair("artist", artist);
+
+        if let Some(count) = count {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("count", &count.to_string());
+        }
+
+        let songs = subsonic_request(url)
+            .await?
+            .top_songs
+            .on_missing("top_songs")?
+            .song
+            .unwrap_or_else(Vec::new);
+
+        Ok(songs)
+    }
+
+    /// Returns the queue saved by any client for this user, if there is one.
+    pub async fn play_queue(&self) -> Result<Option<PlayQueue>> {
+        let url = self.base_url("getPlayQueue")?;
+
+        Ok(subsonic_request(url).await?.play_queue)
+    }
+
+    /// Saves the queue so another client can resume it. An empty queue clears it.
+    pub async fn save_play_queue(
+        &self,
+        ids: &[SongId],
+        current: Option<&SongId>,
+        position: Option<u64>,
+    ) -> Result<()> {
+        let mut url = self.base_url("savePlayQueue")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            for id in ids {
+                qp.append_pair("id", id.get_ref());
+            }
+
+            if let Some(current) = current {
+                qp.append_pair("current", current.get_ref());
+            }
+
+            if let Some(position) = position {
+                qp.append_pair("position", &position.to_string());
+            }
+        }
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn bookmarks(&self) -> Result<Vec<Bookmark>> {
+        let url = self.base_url("getBookmarks")?;
+
+        let bookmarks = subsonic_request(url)
+            .await?
+            .bookmarks
+            .on_missing("bookmarks")?
+            .bookmark
+            .unwrap_or_else(Vec::new);
+
+        Ok(bookmarks)
+    }
+
+    /// Creates or replaces the bookmark for a song, with `position` in milliseconds.
+    pub async fn create_bookmark(
+        &self,
+        id: &SongId,
+        position: u64,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let mut url = self.base_url("createBookmark")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("id", id.get_ref());
+            qp.append_pair("position", &position.to_string());
+
+            if let Some(comment) = comment {
+                qp.append_pair("comment", comment);
+            }
+        }
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_bookmark(&self, id: &SongId) -> Result<()> {
+        let mut url = self.base_url("deleteBookmark")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    /// Structured lyrics from the OpenSubsonic songLyrics extension.
+    pub async fn lyrics_by_song_id(&self, id: &SongId) -> Result<Vec<StructuredLyrics>> {
+        let mut url = self.base_url("getLyricsBySongId")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        let lyrics = subsonic_request(url)
+            .await?
+            .lyrics_list
+            .on_missing("lyrics_list")?
+            .structured_lyrics
+            .unwrap_or_else(Vec::new);
+
+        Ok(lyrics)
+    }
+
+    pub async fn lyrics(&self, artist: Option<&str>, title: Option<&str>) -> Result<ClassicLyrics> {
+        let mut url = self.base_url("getLyrics")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            if let Some(artist) = artist {
+                qp.append_pair("artist", artist);
+            }
+
+            if let Some(title) = title {
+                qp.append_pair("title", title);
+            }
+        }
+
+        let lyrics = subsonic_request(url).await?.lyrics.on_missing("lyrics")?;
+
+        Ok(lyrics)
+    }
+
+    pub async fn podcasts(
+        &self,
+        include_episodes: Option<bool>,
+        id: Option<&PodcastChannelId>,
+    ) -> Result<Vec<PodcastChannel>> {
+        let mut url = self.base_url("getPodcasts")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            if let Some(include_episodes) = include_episodes {
+                qp.append_pair("includeEpisodes", &include_episodes.to_string());
+            }
+
+            if let Some(id) = id {
+                qp.append_pair("id", id.get_ref());
+            }
+        }
+
+        let channels = subsonic_request(url)
+            .await?
+            .podcasts
+            .on_missing("podcasts")?
+            .channel
+            .unwrap_or_else(Vec::new);
+
+        Ok(channels)
+    }
+
+    pub async fn newest_podcasts(&self, count: Option<u64>) -> Result<Vec<PodcastEpisode>> {
+        let mut url = self.base_url("getNewestPodcasts")?;
+
+        if let Some(count) = count {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair("count", &count.to_string());
+        }
+
+        let episodes = subsonic_request(url)
+            .await?
+            .newest_podcasts
+            .on_missing("newest_podcasts")?
+            .episode
+            .unwrap_or_else(Vec::new);
+
+        Ok(episodes)
+    }
+
+    pub async fn refresh_podcasts(&self) -> Result<()> {
+        subsonic_request(self.base_url("refreshPodcasts")?).await?;
+
+        Ok(())
+    }
+
+    pub async fn create_podcast_channel(&self, feed_url: &str) -> Result<()> {
+        let mut url = self.base_url("createPodcastChannel")?;
+
+        url.query_pairs_mut().append_pair("url", feed_url);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_podcast_channel(&self, id: &PodcastChannelId) -> Result<()> {
+        let mut url = self.base_url("deletePodcastChannel")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    /// Asks the server to download an episode, after which it gets a `stream_id`.
+    pub async fn download_podcast_episode(&self, id: &PodcastEpisodeId) -> Result<()> {
+        let mut url = self.base_url("downloadPodcastEpisode")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_podcast_episode(&self, id: &PodcastEpisodeId) -> Result<()> {
+        let mut url = self.base_url("deletePodcastEpisode")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn internet_radio_stations(&self) -> Result<Vec<InternetRadioStation>> {
+        let url = self.base_url("getInternetRadioStations")?;
+
+        let stations = subsonic_request(url)
+            .await?
+            .internet_radio_stations
+            .on_missing("internet_radio_stations")?
+            .internet_radio_station
+            .unwrap_or_else(Vec::new);
+
+        Ok(stations)
+    }
+
+    pub async fn create_internet_radio_station(
+        &self,
+        stream_url: &str,
+        name: &str,
+        home_page_url: Option<&str>,
+    ) -> Result<()> {
+        let mut url = self.base_url("createInternetRadioStation")?;
+
+        write_station(&mut url, stream_url, name, home_page_url);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_internet_radio_station(
+        &self,
+        id: &RadioStationId,
+        stream_url: &str,
+        name: &str,
+        home_page_url: Option<&str>,
+    ) -> Result<()> {
+        let mut url = self.base_url("updateInternetRadioStation")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+        write_station(&mut url, stream_url, name, home_page_url);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_internet_radio_station(&self, id: &RadioStationId) -> Result<()> {
+        let mut url = self.base_url("deleteInternetRadioStation")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn shares(&self) -> Result<Vec<Share>> {
+        let url = self.base_url("getShares")?;
+
+        let shares = subsonic_request(url)
+            .await?
+            .shares
+            .on_missing("shares")?
+            .share
+            .unwrap_or_else(Vec::new);
+
+        Ok(shares)
+    }
+
+    /// Shares songs, albums or directories by ID, returning the new share with its public URL.
+    pub async fn create_share(
+        &self,
+        ids: &[String],
+        description: Option<&str>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<Share> {
+        let mut url = self.base_url("createShare")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            for id in ids {
+                qp.append_pair("id", id);
+            }
+        }
+
+        write_share(&mut url, description, expires);
+
+        let share = subsonic_request(url)
+            .await?
+            .shares
+            .and_then(|shares| shares.share)
+            .and_then(|shares| shares.into_iter().next())
+            .on_missing("share")?;
+
+        Ok(share)
+    }
+
+    pub async fn update_share(
+        &self,
+        id: &ShareId,
+        description: Option<&str>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let mut url = self.base_url("updateShare")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+        write_share(&mut url, description, expires);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_share(&self, id: &ShareId) -> Result<()> {
+        let mut url = self.base_url("deleteShare")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn user(&self, username: &str) -> Result<User> {
+        let mut url = self.base_url("getUser")?;
+
+        url.query_pairs_mut().append_pair("username", username);
+
+        let user = subsonic_request(url).await?.user.on_missing("user")?;
+
+        Ok(user)
+    }
+
+    pub async fn users(&self) -> Result<Vec<User>> {
+        let url = self.base_url("getUsers")?;
+
+        let users = subsonic_request(url)
+            .await?
+            .users
+            .on_missing("users")?
+            .user
+            .unwrap_or_else(Vec::new);
+
+        Ok(users)
+    }
+
+    /// Creates a user, `settings` must include an email address.
+    pub async fn create_user(
+        &self,
+        username: &str,
+        password: &Password,
+        settings: &UserSettings,
+    ) -> Result<()> {
+        if settings.email.is_none() {
+            bail!("Creating user {username} requires an email address.");
+        }
+
+        let mut url = self.base_url("createUser")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("username", username);
+            qp.append_pair("password", &encode_password(password));
+        }
+
+        settings.write_to_url(&mut url);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_user(
+        &self,
+        username: &str,
+        password: Option<&Password>,
+        settings: &UserSettings,
+    ) -> Result<()> {
+        let mut url = self.base_url("updateUser")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("username", username);
+
+            if let Some(password) = password {
+                qp.append_pair("password", &encode_password(password));
+            }
+        }
+
+        settings.write_to_url(&mut url);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, username: &str) -> Result<()> {
+        let mut url = self.base_url("deleteUser")?;
+
+        url.query_pairs_mut().append_pair("username", username);
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    pub async fn change_password(&self, username: &str, password: &Password) -> Result<()> {
+        let mut url = self.base_url("changePassword")?;
+
+        {
+            let mut qp = url.query_pairs_mut();
+
+            qp.append_pair("username", username);
+            qp.append_pair("password", &encode_password(password));
+        }
+
+        subsonic_request(url).await?;
+
+        Ok(())
+    }
+
+    /// Controls the server's jukebox, returning its playlist for `Get` and its status otherwise.
+    pub async fn jukebox_control(&self, action: &JukeboxAction) -> Result<JukeboxStatus> {
+        let mut url = self.base_url("jukeboxControl")?;
+
+        action.write_to_url(&mut url);
+
+        let response = subsonic_request(url).await?;
+
+        response
+            .jukebox_playlist
+            .or(response.jukebox_status)
+            .on_missing("jukebox_status")
+    }
+
+    /// Fetches the original file, without transcoding, from byte `offset` onwards if given.
+    pub async fn download(&self, id: &SongId, offset: Option<u64>) -> Result<reqwest::Response> {
+        let mut url = self.base_url("download")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        let mut request = reqwest::Client::new().get(url);
+
+        if let Some(offset) = offset {
+            request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(reqwest::Error::without_url)?;
+
+        // Errors such as an unknown ID come back as a regular Subsonic response.
+        let is_json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+
+        if is_json {
+            check_response(serde_json::from_str(&response.text().await?)?)?;
+            bail!("The server sent a Subsonic response instead of the file.");
+        }
+
+        Ok(response)
+    }
+
+    pub async fn stream(
+        &self,
+        id: &SongId,
+        estimate_content_length: Option<bool>,
+    ) -> Result<reqwest::Response> {
+        let mut url = self.base_url("stream")?;
+
+        url.query_pairs_mut().append_pair("id", id.get_ref());
+
+        if let Some(estimate_content_length) = estimate_content_length {
+            let mut qp = url.query_pairs_mut();
+            qp.append_pair(
+                "estimateContentLength",
+                &estimate_content_length.to_string(),
+            );
+        }
+
+        Ok(reqwest::get(url)
+            .await
+            .map_err(reqwest::Error::without_url)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{PasswordHash, Salt};
+
+    use super::*;
+
+    #[test]
+    fn test_base_url() -> Result<()> {
+        let client = SubsonicClient {
+            url: ServerUrl::unchecked("https://subsonic.example.com"),
+            username: Username::unchecked("user"),
+            token_info: TokenInfo {
+                hash: PasswordHash::unchecked("a1b2c3"),
+                salt: Salt::unchecked("abcde"),
+            },
+        };
+
+        let base_url = client.base_url("ping")?;
+
+        assert_eq!(base_url, Url::parse("https://subsonic.example.com/rest/ping?f=json&u=user&t=a1b2c3&s=abcde&v=1.16.1&c=knuckles")?);
+
+        Ok(())
+    }
+
+    fn client_at(url: &str) -> SubsonicClient {
+        SubsonicClient {
+            url: ServerUrl::unchecked(url),
+            username: Username::unchecked("user"),
+            token_info: TokenInfo {
+                hash: PasswordHash::unchecked("a1b2c3"),
+                salt: Salt::unchecked("abcde"),
+            },
+        }
+    }
+
+    #[test]
+    fn test_base_url_variants() -> Result<()> {
+        let cases = [
+            (
+                "https://subsonic.example.com/",
+                "https://subsonic.example.com/rest/ping",
+            ),
+            (
+                "https://example.com/music",
+                "https://example.com/music/rest/ping",
+            ),
+            (
+                "https://example.com/music/",
+                "https://example.com/music/rest/ping",
+            ),
+            (
+                "http://example.com:8080/apps/subsonic/",
+                "http://example.com:8080/apps/subsonic/rest/ping",
+            ),
+        ];
+
+        for (server_url, expected) in cases {
+            let base_url = client_at(server_url).base_url("ping")?;
+
+            assert_eq!(
+                base_url,
+                Url::parse(&format!(
+                    "{expected}?f=json&u=user&t=a1b2c3&s=abcde&v=1.16.1&c=knuckles"
+                ))?,
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_failed_response() -> Result<()> {
+        let response = serde_json::from_str(
+            r#"{
+                "subsonic-response": {
+                    "status": "failed",
+                    "version": "1.16.1",
+                    "type": "navidrome",
+                    "serverVersion": "0.51.1",
+                    "openSubsonic": true,
+                    "error": { "code": 40, "message": "Wrong username or password" }
+                }
+            }"#,
+        )?;
+
+        match check_response(response) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Subsonic error 40: Wrong username or password"
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_password() {
+        assert_eq!(
+            encode_password(&Password::unchecked("sesame")),
+            "enc:736573616d65"
+        );
+        assert_eq!(encode_password(&Password::unchecked("ü")), "enc:c3bc");
+    }
+
+    #[test]
+    fn test_jukebox_action_into_url() -> Result<()> {
+        let base_url = Url::parse("https://subsonic.example.com/rest/jukeboxControl")?;
+
+        let url_for = |action: JukeboxAction| {
+            let mut url = base_url.clone();
+            action.write_to_url(&mut url);
+            url.query().unwrap_or_default().to_owned()
+        };
+
+        assert_eq!(url_for(JukeboxAction::Status), "action=status");
+        assert_eq!(
+            url_for(JukeboxAction::Set(vec![
+                SongId::unchecked("a"),
+                SongId::unchecked("b")
+            ])),
+            "action=set&id=a&id=b"
+        );
+        assert_eq!(
+            url_for(JukeboxAction::Skip {
+                index: 3,
+                offset: Some(90)
+            }),
+            "action=skip&index=3&offset=90"
+        );
+        assert_eq!(
+            url_for(JukeboxAction::Skip {
+                index: 0,
+                offset: None
+            }),
+            "action=skip&index=0"
+        );
+        assert_eq!(url_for(JukeboxAction::Remove(2)), "action=remove&index=2");
+        assert_eq!(
+            url_for(JukeboxAction::SetGain(0.5)),
+            "action=setGain&gain=0.5"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_songs_filter_into_url() -> Result<()> {
+        let base_url = Url::parse("https://subsonic.example.com/rest/getRandomSongs")?;
+
+        {
+            let mut url = base_url.clone();
+
+            RandomSongsFilter::default().write_to_url(&mut url);
+
+            assert_eq!(
+                url,
+                Url::parse("https://subsonic.example.com/rest/getRandomSongs?")?
+            );
+        }
+
+        {
+            let mut url = base_url.clone();
+
+            RandomSongsFilter {
+                genre: Some("Jazz".to_owned()),
+                from_year: Some(1970),
+                to_year: Some(1979),
+                music_folder_id: Some(MusicFolderId::unchecked("1")),
+            }
+            .write_to_url(&mut url);
+
+            assert_eq!(
+                url,
+                Url::parse(
+                    "https://subsonic.example.com/rest/getRandomSongs?genre=Jazz&fromYear=1970&toYear=1979&musicFolderId=1",
+                )?,
+            );
+        }
 
         Ok(())
     }
@@ -230,8 +1419,8 @@ mod tests {
             let mut url = base_url.clone();
 
             AlbumListType::ByYear {
-                from_year: "2019".to_owned(),
-                to_year: "2022".to_owned(),
+                from_year: 2019,
+                to_year: 2022,
             }
             .write_to_url(&mut url);
 
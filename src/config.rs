@@ -1,35 +1,132 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::client::SubsonicClient;
 use crate::hash::Hasher;
+use crate::layers::ConfigLayers;
+use crate::password::{password_from_command, password_from_env, password_from_keyring};
+use crate::smart::SmartPlaylist;
 use crate::token::TokenInfo;
-use crate::types::{Password, ServerUrl, Username};
+use crate::types::{Password, ServerUrl, Strong, Username};
 
 const DEFAULT_CONFIG_FILENAME: &str = "knuckles.toml";
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct KeyringInfo {
+    pub service: String,
+    pub user: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum AuthInfo {
     Password(Password),
+    PasswordCommand(String),
+    PasswordEnv(String),
+    Keyring(KeyringInfo),
     Token(TokenInfo),
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub struct SubsonicConfig {
-    url: ServerUrl,
-    username: Username,
+    pub url: ServerUrl,
+    pub username: Username,
     #[serde(flatten)]
-    auth_info: AuthInfo,
+    pub auth_info: AuthInfo,
+}
+
+impl SubsonicConfig {
+    pub fn check_fields(table: &toml::Table) -> Result<(), (&'static str, toml::de::Error)> {
+        if let Some(url) = table.get("url") {
+            ServerUrl::deserialize(url.clone()).map_err(|e| ("url", e))?;
+        }
+
+        if let Some(username) = table.get("username") {
+            Username::deserialize(username.clone()).map_err(|e| ("username", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct PlayerConfig {
+    /// Songs at least this many seconds long are bookmarked as they play, 0 disables bookmarks.
+    pub bookmark_threshold: u64,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct DownloadConfig {
+    /// File name template for downloaded songs, see `FileNameTemplate`.
+    pub template: String,
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 pub struct Config {
-    pub client: SubsonicConfig,
+    pub client: Option<SubsonicConfig>,
+    pub default_server: Option<String>,
+    pub download: DownloadConfig,
+    pub player: PlayerConfig,
+    #[serde(default)]
+    pub servers: BTreeMap<String, SubsonicConfig>,
+    #[serde(default)]
+    pub smart_playlists: BTreeMap<String, SmartPlaylist>,
+}
+
+impl Config {
+    /// The top-level configuration keys, one for each field of `Config`.
+    pub const KEYS: &'static [&'static str] = &[
+        "client",
+        "default_server",
+        "download",
+        "player",
+        "servers",
+        "smart_playlists",
+    ];
+
+    fn profile_names(&self) -> String {
+        if self.servers.is_empty() {
+            return "none".to_owned();
+        }
+
+        self.servers
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn server(&self, profile: Option<&str>) -> Result<&SubsonicConfig> {
+        if let Some(name) = profile.or(self.default_server.as_deref()) {
+            return self.servers.get(name).with_context(|| {
+                format!(
+                    "No server profile named {name}. Available profiles: {}.",
+                    self.profile_names()
+                )
+            });
+        }
+
+        if let Some(client) = &self.client {
+            return Ok(client);
+        }
+
+        let mut servers = self.servers.values();
+
+        match (servers.next(), servers.next()) {
+            (Some(server), None) => Ok(server),
+            (None, _) => bail!("No server configured. Add a [client] or [servers.<name>] section."),
+            (Some(_), Some(_)) => bail!(
+                "Multiple server profiles configured, select one with --profile or set default_server. Available profiles: {}.",
+                self.profile_names()
+            ),
+        }
+    }
 }
 
 pub fn make_candidate_config_path(base: &Path) -> PathBuf {
@@ -46,27 +143,128 @@ pub fn default_config_file_path() -> Result<PathBuf> {
     Ok(make_candidate_config_path(&default_config_directory()?))
 }
 
-pub fn read_config_from_string(config: &str) -> Result<Config> {
-    Ok(toml::from_str(config)?)
+#[cfg(not(tarpaulin_include))]
+pub fn system_config_file_path() -> Option<PathBuf> {
+    if cfg!(unix) {
+        Some(make_candidate_config_path(Path::new("/etc/knuckles")))
+    } else {
+        None
+    }
 }
 
+/// Reads a single configuration file on top of the built-in defaults.
 pub fn read_config_from_path(config_path: &Path) -> Result<Config> {
-    read_config_from_string(&fs::read_to_string(config_path)?)
+    let mut layers = ConfigLayers::new()?;
+    layers.add_file(config_path)?;
+
+    layers.config()
+}
+
+fn read_document(config_path: &Path) -> Result<toml_edit::DocumentMut> {
+    match fs::read_to_string(config_path) {
+        Ok(text) => Ok(text.parse()?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(toml_edit::DocumentMut::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn config_has_server(config_path: &Path, profile: Option<&str>) -> Result<bool> {
+    let document = read_document(config_path)?;
+
+    let section = match profile {
+        Some(name) => document
+            .get("servers")
+            .and_then(|servers| servers.get(name)),
+        None => document.get("client"),
+    };
+
+    Ok(section.is_some())
+}
+
+fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(0o600);
+
+        // The mode only applies to newly created files, so tighten existing ones too.
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(contents.as_bytes())?;
+
+    Ok(())
+}
+
+pub fn write_server_config(
+    config_path: &Path,
+    profile: Option<&str>,
+    server: &SubsonicConfig,
+) -> Result<()> {
+    let mut document = read_document(config_path)
+        .with_context(|| format!("Could not read {}.", config_path.display()))?;
+
+    let section: toml_edit::DocumentMut = toml::to_string(server)?.parse()?;
+    let section = toml_edit::Item::Table(section.as_table().clone());
+
+    match profile {
+        Some(name) => {
+            let servers = document.entry("servers").or_insert_with(|| {
+                let mut servers = toml_edit::Table::new();
+                servers.set_implicit(true);
+                toml_edit::Item::Table(servers)
+            });
+
+            let Some(servers) = servers.as_table_mut() else {
+                bail!("`servers` in {} is not a table.", config_path.display());
+            };
+
+            servers.insert(name, section);
+        }
+        None => {
+            document.insert("client", section);
+        }
+    }
+
+    write_private_file(config_path, &document.to_string())
+        .with_context(|| format!("Could not write {}.", config_path.display()))
 }
 
-pub fn make_client<R: Rng>(config: &Config, hasher: &mut Hasher<R>) -> SubsonicClient {
+pub fn make_client<R: Rng>(
+    server: &SubsonicConfig,
+    hasher: &mut Hasher<R>,
+) -> Result<SubsonicClient> {
     use AuthInfo::*;
 
-    let token_info = match &config.client.auth_info {
+    let username = &server.username;
+
+    let token_info = match &server.auth_info {
         Password(password) => hasher.md5_with_random_salt(password),
+        PasswordCommand(command) => hasher.md5_with_random_salt(&password_from_command(command)?),
+        PasswordEnv(variable) => hasher.md5_with_random_salt(&password_from_env(variable)?),
+        Keyring(KeyringInfo { service, user }) => {
+            let user = user.as_deref().unwrap_or(username.get_ref());
+
+            hasher.md5_with_random_salt(&password_from_keyring(service, user)?)
+        }
         Token(token_info) => token_info.clone(),
     };
 
-    SubsonicClient {
-        url: config.client.url.clone(),
-        username: config.client.username.clone(),
+    Ok(SubsonicClient {
+        url: server.url.clone(),
+        username: username.clone(),
         token_info,
-    }
+    })
 }
 
 #[cfg(test)]
@@ -76,18 +274,39 @@ mod tests {
     use textwrap::dedent;
 
     use crate::{
+        layers::Source,
+        smart::Order,
         test_util::test_data_path,
         types::{PasswordHash, Salt},
     };
 
     use super::*;
 
+    fn read_config_from_string(config: &str) -> Result<Config> {
+        let mut layers = ConfigLayers::new()?;
+        layers.add_text(Source::File(PathBuf::from("knuckles.toml")), config)?;
+
+        layers.config()
+    }
+
+    fn download_config() -> DownloadConfig {
+        DownloadConfig {
+            template: "{artist}/{year} - {album}/{disc}-{track} {title}.{suffix}".to_owned(),
+        }
+    }
+
+    fn player_config() -> PlayerConfig {
+        PlayerConfig {
+            bookmark_threshold: 1200,
+        }
+    }
+
     #[test]
     fn test_read_well_formed_config_with_password() -> Result<()> {
         let config_text = dedent(
             r#"
             [client]
-            url = "dummyurl"
+            url = "https://subsonic.example.com"
             username = "test"
             password = "password"
         "#,
@@ -96,11 +315,16 @@ mod tests {
         let config = read_config_from_string(&config_text)?;
 
         let expected = Config {
-            client: SubsonicConfig {
-                url: ServerUrl::unchecked("dummyurl"),
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
                 username: Username::unchecked("test"),
                 auth_info: AuthInfo::Password(Password::unchecked("password")),
-            },
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
         };
 
         assert_eq!(config, expected);
@@ -113,7 +337,7 @@ mod tests {
         let config_text = dedent(
             r#"
             [client]
-            url = "dummyurl"
+            url = "https://subsonic.example.com"
             username = "test"
 
             [client.token]
@@ -125,14 +349,19 @@ mod tests {
         let config = read_config_from_string(&config_text)?;
 
         let expected = Config {
-            client: SubsonicConfig {
-                url: ServerUrl::unchecked("dummyurl"),
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
                 username: Username::unchecked("test"),
                 auth_info: AuthInfo::Token(TokenInfo {
                     hash: PasswordHash::unchecked("a1b2c3"),
                     salt: Salt::unchecked("abcde"),
                 }),
-            },
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
         };
 
         assert_eq!(config, expected);
@@ -140,6 +369,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_config_with_invalid_url() {
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "dummyurl"
+            username = "test"
+            password = "password"
+        "#,
+        );
+
+        match read_config_from_string(&config_text) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Invalid value for `client.url` (file knuckles.toml, line 3): `dummyurl` is not a valid URL (relative URL without a base)."
+            ),
+        }
+    }
+
     #[test]
     fn test_make_client_with_config_password() -> Result<()> {
         let rng = rand::rngs::StdRng::seed_from_u64(10);
@@ -148,7 +397,7 @@ mod tests {
         let config_text = dedent(
             r#"
             [client]
-            url = "dummyurl"
+            url = "https://subsonic.example.com"
             username = "test"
             password = "password"
         "#,
@@ -156,10 +405,10 @@ mod tests {
 
         let config = read_config_from_string(&config_text)?;
 
-        let client = make_client(&config, &mut hasher);
+        let client = make_client(config.server(None)?, &mut hasher)?;
 
         let expected = SubsonicClient {
-            url: ServerUrl::unchecked("dummyurl"),
+            url: ServerUrl::unchecked("https://subsonic.example.com/"),
             username: Username::unchecked("test"),
             token_info: TokenInfo {
                 hash: PasswordHash::unchecked("cc4574efec464ba75cce2c1c36a6e028"),
@@ -180,7 +429,7 @@ mod tests {
         let config_text = dedent(
             r#"
             [client]
-            url = "dummyurl"
+            url = "https://subsonic.example.com"
             username = "test"
 
             [client.token]
@@ -191,10 +440,10 @@ mod tests {
 
         let config = read_config_from_string(&config_text)?;
 
-        let client = make_client(&config, &mut hasher);
+        let client = make_client(config.server(None)?, &mut hasher)?;
 
         let expected = SubsonicClient {
-            url: ServerUrl::unchecked("dummyurl"),
+            url: ServerUrl::unchecked("https://subsonic.example.com/"),
             username: Username::unchecked("test"),
             token_info: TokenInfo {
                 hash: PasswordHash::unchecked("a1b2c3"),
@@ -207,6 +456,494 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_well_formed_config_with_password_command() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "https://subsonic.example.com"
+            username = "test"
+            password_command = "pass show music"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let expected = Config {
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
+                username: Username::unchecked("test"),
+                auth_info: AuthInfo::PasswordCommand("pass show music".to_owned()),
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
+        };
+
+        assert_eq!(config, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_well_formed_config_with_password_env() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "https://subsonic.example.com"
+            username = "test"
+            password_env = "KNUCKLES_PASSWORD"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let expected = Config {
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
+                username: Username::unchecked("test"),
+                auth_info: AuthInfo::PasswordEnv("KNUCKLES_PASSWORD".to_owned()),
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
+        };
+
+        assert_eq!(config, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_well_formed_config_with_keyring() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "https://subsonic.example.com"
+            username = "test"
+
+            [client.keyring]
+            service = "knuckles"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let expected = Config {
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
+                username: Username::unchecked("test"),
+                auth_info: AuthInfo::Keyring(KeyringInfo {
+                    service: "knuckles".to_owned(),
+                    user: None,
+                }),
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
+        };
+
+        assert_eq!(config, expected);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_make_client_with_config_password_command() -> Result<()> {
+        let rng = rand::rngs::StdRng::seed_from_u64(10);
+        let mut hasher = Hasher::new(rng);
+
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "https://subsonic.example.com"
+            username = "test"
+            password_command = "echo password"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let client = make_client(config.server(None)?, &mut hasher)?;
+
+        let expected = SubsonicClient {
+            url: ServerUrl::unchecked("https://subsonic.example.com/"),
+            username: Username::unchecked("test"),
+            token_info: TokenInfo {
+                hash: PasswordHash::unchecked("cc4574efec464ba75cce2c1c36a6e028"),
+                salt: Salt::unchecked("YIVLnWx"),
+            },
+        };
+
+        assert_eq!(client, expected);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_make_client_with_failing_password_command() -> Result<()> {
+        let mut hasher = Hasher::new(rand::rngs::StdRng::seed_from_u64(10));
+
+        let config_text = dedent(
+            r#"
+            [client]
+            url = "https://subsonic.example.com"
+            username = "test"
+            password_command = "exit 1"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        match make_client(config.server(None)?, &mut hasher) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Password command `exit 1` failed (exit status: 1)."
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_well_formed_config_with_profiles() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            default_server = "home"
+
+            [servers.home]
+            url = "https://home.example.com"
+            username = "test"
+            password = "password"
+
+            [servers.office]
+            url = "https://office.example.com"
+            username = "test.office"
+            password_env = "KNUCKLES_OFFICE_PASSWORD"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let expected = Config {
+            client: None,
+            default_server: Some("home".to_owned()),
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::from([
+                (
+                    "home".to_owned(),
+                    SubsonicConfig {
+                        url: ServerUrl::unchecked("https://home.example.com/"),
+                        username: Username::unchecked("test"),
+                        auth_info: AuthInfo::Password(Password::unchecked("password")),
+                    },
+                ),
+                (
+                    "office".to_owned(),
+                    SubsonicConfig {
+                        url: ServerUrl::unchecked("https://office.example.com/"),
+                        username: Username::unchecked("test.office"),
+                        auth_info: AuthInfo::PasswordEnv("KNUCKLES_OFFICE_PASSWORD".to_owned()),
+                    },
+                ),
+            ]),
+            smart_playlists: BTreeMap::new(),
+        };
+
+        assert_eq!(config, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys() -> Result<()> {
+        // Naming every field stops this from compiling when one is added to `Config`, until
+        // it is listed here and in `Config::KEYS` as well.
+        let Config {
+            client: _,
+            default_server: _,
+            download: _,
+            player: _,
+            servers: _,
+            smart_playlists: _,
+        } = read_config_from_string("")?;
+
+        assert_eq!(
+            Config::KEYS,
+            [
+                "client",
+                "default_server",
+                "download",
+                "player",
+                "servers",
+                "smart_playlists"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_smart_playlists() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            [smart_playlists.old_jazz]
+            rules = ["starred", "genre = jazz", "year >= 1970", "year < 1980", "last_played > 30d"]
+            order = "least_recently_played"
+            name = "Old Jazz"
+
+            [smart_playlists.everything]
+            limit = 0
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        let old_jazz = &config.smart_playlists["old_jazz"];
+        assert_eq!(
+            old_jazz.description(),
+            "starred, genre = jazz, year >= 1970, year < 1980, last_played > 30d"
+        );
+        assert_eq!(old_jazz.order, Order::LeastRecentlyPlayed);
+        assert_eq!(old_jazz.name.as_deref(), Some("Old Jazz"));
+
+        let everything = &config.smart_playlists["everything"];
+        assert_eq!(everything.description(), "all songs");
+        assert_eq!(everything.limit, 0);
+
+        match read_config_from_string("[smart_playlists.bad]\nrules = [\"mood = happy\"]\n") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Unknown field mood in smart playlist rule `mood = happy`, expected one of starred, genre, year, rating, play_count, last_played, duration, bpm.\nin `smart_playlists.bad.rules`\n"
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_server_profile() -> Result<()> {
+        let config_text = dedent(
+            r#"
+            default_server = "home"
+
+            [servers.home]
+            url = "https://home.example.com"
+            username = "test"
+            password = "password"
+
+            [servers.office]
+            url = "https://office.example.com"
+            username = "test"
+            password = "password"
+        "#,
+        );
+
+        let config = read_config_from_string(&config_text)?;
+
+        assert_eq!(
+            config.server(None)?.url,
+            ServerUrl::unchecked("https://home.example.com/")
+        );
+        assert_eq!(
+            config.server(Some("office"))?.url,
+            ServerUrl::unchecked("https://office.example.com/")
+        );
+
+        match config.server(Some("cloud")) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "No server profile named cloud. Available profiles: home, office."
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_server_without_default() -> Result<()> {
+        let single = read_config_from_string(&dedent(
+            r#"
+            [servers.home]
+            url = "https://home.example.com"
+            username = "test"
+            password = "password"
+        "#,
+        ))?;
+
+        assert_eq!(
+            single.server(None)?.url,
+            ServerUrl::unchecked("https://home.example.com/")
+        );
+
+        let with_client = read_config_from_string(&dedent(
+            r#"
+            [client]
+            url = "https://client.example.com"
+            username = "test"
+            password = "password"
+
+            [servers.home]
+            url = "https://home.example.com"
+            username = "test"
+            password = "password"
+        "#,
+        ))?;
+
+        assert_eq!(
+            with_client.server(None)?.url,
+            ServerUrl::unchecked("https://client.example.com/")
+        );
+
+        let multiple = read_config_from_string(&dedent(
+            r#"
+            [servers.home]
+            url = "https://home.example.com"
+            username = "test"
+            password = "password"
+
+            [servers.office]
+            url = "https://office.example.com"
+            username = "test"
+            password = "password"
+        "#,
+        ))?;
+
+        match multiple.server(None) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Multiple server profiles configured, select one with --profile or set default_server. Available profiles: home, office."
+            ),
+        }
+
+        match read_config_from_string("")?.server(None) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "No server configured. Add a [client] or [servers.<name>] section."
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_server_config() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let config_path = directory.path().join("knuckles/knuckles.toml");
+
+        let server = SubsonicConfig {
+            url: ServerUrl::unchecked("https://subsonic.example.com/"),
+            username: Username::unchecked("test"),
+            auth_info: AuthInfo::Password(Password::unchecked("password")),
+        };
+
+        assert!(!config_has_server(&config_path, None)?);
+
+        write_server_config(&config_path, None, &server)?;
+
+        assert!(config_has_server(&config_path, None)?);
+        assert_eq!(
+            fs::read_to_string(&config_path)?,
+            dedent(
+                r#"
+                [client]
+                url = "https://subsonic.example.com/"
+                username = "test"
+                password = "password"
+            "#
+            )
+            .trim_start()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&config_path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_server_config_profile_keeps_existing_content() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let config_path = directory.path().join("knuckles.toml");
+
+        fs::write(
+            &config_path,
+            dedent(
+                r#"
+                # Shared by the whole team.
+                default_server = "office"
+
+                [servers.office]
+                url = "https://office.example.com/"
+                username = "test"
+                password_command = "pass show office"
+            "#,
+            ),
+        )?;
+
+        let server = SubsonicConfig {
+            url: ServerUrl::unchecked("https://home.example.com/"),
+            username: Username::unchecked("test"),
+            auth_info: AuthInfo::Token(TokenInfo {
+                hash: PasswordHash::unchecked("a1b2c3"),
+                salt: Salt::unchecked("abcde"),
+            }),
+        };
+
+        assert!(!config_has_server(&config_path, Some("home"))?);
+
+        write_server_config(&config_path, Some("home"), &server)?;
+
+        assert!(config_has_server(&config_path, Some("home"))?);
+        assert_eq!(
+            fs::read_to_string(&config_path)?,
+            dedent(
+                r#"
+                # Shared by the whole team.
+                default_server = "office"
+
+                [servers.office]
+                url = "https://office.example.com/"
+                username = "test"
+                password_command = "pass show office"
+
+                [servers.home]
+                url = "https://home.example.com/"
+                username = "test"
+
+                [servers.home.token]
+                hash = "a1b2c3"
+                salt = "abcde"
+            "#,
+            )
+        );
+
+        let config = read_config_from_path(&config_path)?;
+
+        assert_eq!(config.server(Some("home"))?, &server);
+
+        Ok(())
+    }
+
     #[test]
     fn test_make_candidate_config_path() {
         assert_eq!(
@@ -222,14 +959,19 @@ mod tests {
         let config = read_config_from_path(&config_path)?;
 
         let expected = Config {
-            client: SubsonicConfig {
-                url: ServerUrl::unchecked("dummyurl"),
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
                 username: Username::unchecked("test"),
                 auth_info: AuthInfo::Token(TokenInfo {
                     hash: PasswordHash::unchecked("a1b2c3"),
                     salt: Salt::unchecked("abcde"),
                 }),
-            },
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
         };
 
         assert_eq!(config, expected);
@@ -244,11 +986,16 @@ mod tests {
         let config = read_config_from_path(&config_path)?;
 
         let expected = Config {
-            client: SubsonicConfig {
-                url: ServerUrl::unchecked("dummyurl"),
+            client: Some(SubsonicConfig {
+                url: ServerUrl::unchecked("https://subsonic.example.com/"),
                 username: Username::unchecked("test"),
                 auth_info: AuthInfo::Password(Password::unchecked("password")),
-            },
+            }),
+            default_server: None,
+            download: download_config(),
+            player: player_config(),
+            servers: BTreeMap::new(),
+            smart_playlists: BTreeMap::new(),
         };
 
         assert_eq!(config, expected);
@@ -0,0 +1,296 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use reqwest::StatusCode;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use crate::api_types::Song;
+use crate::client::SubsonicClient;
+use crate::error::ValidationError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Artist,
+    Album,
+    Year,
+    Disc,
+    Track,
+    Title,
+    Suffix,
+    Genre,
+    Id,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 9] = [
+        ("artist", Field::Artist),
+        ("album", Field::Album),
+        ("year", Field::Year),
+        ("disc", Field::Disc),
+        ("track", Field::Track),
+        ("title", Field::Title),
+        ("suffix", Field::Suffix),
+        ("genre", Field::Genre),
+        ("id", Field::Id),
+    ];
+
+    fn value(self, song: &Song) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "Unknown".to_owned());
+
+        match self {
+            Field::Artist => text(&song.artist),
+            Field::Album => text(&song.album),
+            Field::Year => song
+                .year
+                .map_or_else(|| "Unknown".to_owned(), |year| year.to_string()),
+            Field::Disc => song.disc_number.unwrap_or(1).to_string(),
+            Field::Track => format!("{:02}", song.track.unwrap_or_default()),
+            Field::Title => song.title.clone(),
+            Field::Suffix => song_suffix(song),
+            Field::Genre => text(&song.genre),
+            Field::Id => song.id.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// Where a song is saved, relative to the download directory, with `/` separating
+/// directories and fields such as `{artist}` filled in from the song.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileNameTemplate {
+    parts: Vec<Part>,
+}
+
+impl FileNameTemplate {
+    pub fn new(template: &str) -> Result<Self, ValidationError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Text(rest[..open].to_owned()));
+            }
+
+            let close = rest[open..].find('}').ok_or_else(|| {
+                ValidationError(format!("Unclosed {{ in download template {template}."))
+            })?;
+            let name = &rest[open + 1..open + close];
+
+            let field = Field::ALL
+                .iter()
+                .find(|(field_name, _)| *field_name == name)
+                .map(|(_, field)| *field)
+                .ok_or_else(|| {
+                    let names: Vec<_> = Field::ALL.iter().map(|(name, _)| *name).collect();
+
+                    ValidationError(format!(
+                        "Unknown field {{{name}}} in download template, expected one of {}.",
+                        names.join(", ")
+                    ))
+                })?;
+
+            parts.push(Part::Field(field));
+            rest = &rest[open + close + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    pub fn path_for(&self, song: &Song) -> PathBuf {
+        let mut components = vec![String::new()];
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => {
+                    let mut pieces = text.split('/');
+
+                    if let (Some(last), Some(piece)) = (components.last_mut(), pieces.next()) {
+                        last.push_str(piece);
+                    }
+
+                    components.extend(pieces.map(str::to_owned));
+                }
+                Part::Field(field) => {
+                    if let Some(last) = components.last_mut() {
+                        last.push_str(&field.value(song));
+                    }
+                }
+            }
+        }
+
+        components
+            .iter()
+            .map(|component| sanitize(component))
+            .filter(|component| !component.is_empty())
+            .collect()
+    }
+}
+
+fn song_suffix(song: &Song) -> String {
+    song.suffix
+        .clone()
+        .or_else(|| {
+            let path = Path::new(song.path.as_deref()?);
+
+            Some(path.extension()?.to_str()?.to_owned())
+        })
+        .unwrap_or_else(|| "bin".to_owned())
+}
+
+// Keeps a path component valid on common file systems and unable to leave the
+// download directory.
+fn sanitize(component: &str) -> String {
+    let replaced: String = component
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = replaced.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    trimmed.to_owned()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Downloaded,
+    Resumed,
+    Skipped,
+}
+
+/// Saves a song's original file to `path`, skipping it if a file of the same size is
+/// there and continuing it if a shorter one is.
+pub async fn download_song(client: &SubsonicClient, song: &Song, path: &Path) -> Result<Outcome> {
+    let existing = fs::metadata(path).await.ok().map(|metadata| metadata.len());
+
+    let offset = match (existing, song.size) {
+        (Some(existing), Some(size)) if existing == size => return Ok(Outcome::Skipped),
+        (Some(existing), Some(size)) if existing > 0 && existing < size => Some(existing),
+        _ => None,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let mut response = client.download(&song.id, offset).await?;
+
+    // Servers that ignore the range send the whole file again.
+    let resumed = offset.is_some() && response.status() == StatusCode::PARTIAL_CONTENT;
+
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(path).await?
+    } else {
+        fs::File::create(path).await?
+    };
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+
+    Ok(if resumed {
+        Outcome::Resumed
+    } else {
+        Outcome::Downloaded
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn song() -> Result<Song> {
+        Ok(serde_json::from_value(json!({
+            "id": "s-1",
+            "isDir": false,
+            "title": "Aria: Da Capo?",
+            "album": "Goldberg Variations",
+            "artist": "Glenn Gould",
+            "year": 1981,
+            "discNumber": 1,
+            "track": 32,
+            "suffix": "flac",
+            "path": "Glenn Gould/Goldberg Variations/32.flac"
+        }))?)
+    }
+
+    #[test]
+    fn test_default_template() -> Result<()> {
+        let template =
+            FileNameTemplate::new("{artist}/{year} - {album}/{disc}-{track} {title}.{suffix}")?;
+
+        assert_eq!(
+            template.path_for(&song()?),
+            PathBuf::from("Glenn Gould/1981 - Goldberg Variations/1-32 Aria_ Da Capo_.flac")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_values_cannot_add_directories() -> Result<()> {
+        let template = FileNameTemplate::new("{artist}/{title}")?;
+
+        let mut song = song()?;
+        song.artist = Some("../AC/DC".to_owned());
+
+        assert_eq!(
+            template.path_for(&song),
+            PathBuf::from("_AC_DC/Aria_ Da Capo_")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_fields() -> Result<()> {
+        let template = FileNameTemplate::new("{album}/{track} {title}.{suffix}")?;
+
+        let song: Song = serde_json::from_value(json!({
+            "id": "s-2",
+            "isDir": false,
+            "title": "Untitled",
+            "path": "loose/untitled.mp3"
+        }))?;
+
+        assert_eq!(
+            template.path_for(&song),
+            PathBuf::from("Unknown/00 Untitled.mp3")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert_eq!(
+            FileNameTemplate::new("{artist}/{name}"),
+            Err(ValidationError(
+                "Unknown field {name} in download template, expected one of artist, album, year, disc, track, title, suffix, genre, id.".to_owned()
+            ))
+        );
+        assert_eq!(
+            FileNameTemplate::new("{artist"),
+            Err(ValidationError(
+                "Unclosed { in download template {artist.".to_owned()
+            ))
+        );
+    }
+}
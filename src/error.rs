@@ -1,6 +1,33 @@
 use std::convert::Infallible;
 
 use anyhow::{Context, Result};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{0}")]
+pub struct ValidationError(pub String);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ArgumentError {
+    #[error("{parameter} must be at most {max}, got {value}.")]
+    TooLarge {
+        parameter: &'static str,
+        value: u64,
+        max: u64,
+    },
+}
+
+pub fn check_at_most(parameter: &'static str, value: u64, max: u64) -> Result<u64, ArgumentError> {
+    if value > max {
+        return Err(ArgumentError::TooLarge {
+            parameter,
+            value,
+            max,
+        });
+    }
+
+    Ok(value)
+}
 
 pub trait OnMissing<T, E>: Context<T, E> {
     fn on_missing(self, attribute_name: &str) -> Result<T>;
@@ -21,7 +48,7 @@ mod tests {
         let a: Option<i64> = None;
 
         match a.on_missing("a") {
-            Ok(_) => assert!(false),
+            Ok(_) => panic!("expected an error"),
             Err(e) => assert_eq!(e.to_string(), "Missing optional attribute a."),
         }
 
@@ -29,7 +56,24 @@ mod tests {
 
         match a.on_missing("a") {
             Ok(n) => assert_eq!(n, 42),
-            Err(_) => assert!(false),
+            Err(e) => panic!("unexpected error: {e}"),
         }
     }
+
+    #[test]
+    fn test_check_at_most() {
+        assert_eq!(check_at_most("size", 500, 500), Ok(500));
+        assert_eq!(
+            check_at_most("size", 501, 500),
+            Err(ArgumentError::TooLarge {
+                parameter: "size",
+                value: 501,
+                max: 500
+            })
+        );
+        assert_eq!(
+            check_at_most("size", 501, 500).unwrap_err().to_string(),
+            "size must be at most 500, got 501."
+        );
+    }
 }
@@ -0,0 +1,293 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::api_types::Song;
+use crate::config::SubsonicConfig;
+use crate::types::{SongId, Strong};
+
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE plays (
+        id INTEGER PRIMARY KEY,
+        song_id TEXT NOT NULL,
+        title TEXT NOT NULL,
+        artist TEXT,
+        album TEXT,
+        played_at INTEGER NOT NULL,
+        seconds INTEGER NOT NULL,
+        stopped_early INTEGER NOT NULL
+    );
+    CREATE INDEX plays_played_at ON plays (played_at);
+    CREATE TABLE play_genres (
+        play_id INTEGER NOT NULL REFERENCES plays (id),
+        genre TEXT NOT NULL
+    );
+    CREATE INDEX play_genres_play_id ON play_genres (play_id);
+";
+
+/// Songs count as stopped early when playback was interrupted after less than this, or
+/// less than half of the song, was heard.
+const STOPPED_EARLY_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+
+/// Where the listening history for a server is kept, one file per server and user. Unlike
+/// the library snapshot it cannot be fetched again, so it lives with the user's data.
+pub fn default_history_path(server: &SubsonicConfig) -> Result<PathBuf> {
+    let data = dirs::data_dir().context("Could not find a data directory for this platform.")?;
+    let key = md5::compute(format!("{}@{}", server.username, server.url));
+
+    Ok(data
+        .join("knuckles")
+        .join(format!("history-{key:x}.sqlite3")))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Play {
+    pub song_id: SongId,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genres: Vec<String>,
+    /// When the song started playing.
+    pub played_at: DateTime<Utc>,
+    /// How long the song was heard, which is less than its duration when it was stopped
+    /// early or resumed from a bookmark.
+    pub seconds: u64,
+    /// The player was quit before the song got far, see [`STOPPED_EARLY_THRESHOLD`]. There
+    /// is no way to skip a song while playing, so this is not a skip count.
+    pub stopped_early: bool,
+}
+
+impl Play {
+    pub fn new(song: &Song, played_at: DateTime<Utc>, heard: Duration, finished: bool) -> Self {
+        let threshold = song
+            .duration
+            .map(|duration| Duration::from_secs(duration) / 2)
+            .map_or(STOPPED_EARLY_THRESHOLD, |half| {
+                half.min(STOPPED_EARLY_THRESHOLD)
+            });
+
+        let genres = match &song.genres {
+            Some(genres) if !genres.is_empty() => {
+                genres.iter().map(|genre| genre.name.clone()).collect()
+            }
+            _ => song.genre.iter().cloned().collect(),
+        };
+
+        Self {
+            song_id: song.id.clone(),
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            genres,
+            played_at,
+            seconds: heard.as_secs(),
+            stopped_early: !finished && heard < threshold,
+        }
+    }
+}
+
+/// Every song played on this computer, for listening statistics.
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)
+            .with_context(|| format!("Could not open listening history {}.", path.display()))?;
+
+        Self::from_connection(connection)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        match version {
+            0 => {
+                connection.execute_batch(SCHEMA)?;
+                connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+            SCHEMA_VERSION => {}
+            // The history cannot be fetched again, so never drop it.
+            _ => bail!("The listening history was written by a newer version of knuckles."),
+        }
+
+        Ok(Self { connection })
+    }
+
+    pub fn record(&mut self, play: &Play) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "INSERT INTO plays (song_id, title, artist, album, played_at, seconds, stopped_early)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                play.song_id.get_ref(),
+                play.title,
+                play.artist,
+                play.album,
+                play.played_at.timestamp_millis(),
+                play.seconds,
+                play.stopped_early,
+            ],
+        )?;
+
+        let play_id = transaction.last_insert_rowid();
+
+        for genre in &play.genres {
+            transaction.execute(
+                "INSERT INTO play_genres (play_id, genre) VALUES (?1, ?2)",
+                params![play_id, genre],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Plays from `since` up to but not including `until`, oldest first.
+    pub fn plays(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Play>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, song_id, title, artist, album, played_at, seconds, stopped_early FROM plays
+             WHERE played_at >= ?1 AND played_at < ?2
+             ORDER BY played_at, id",
+        )?;
+        let mut genres = self
+            .connection
+            .prepare("SELECT genre FROM play_genres WHERE play_id = ?1 ORDER BY rowid")?;
+
+        let since = since.map_or(i64::MIN, |since| since.timestamp_millis());
+        let until = until.map_or(i64::MAX, |until| until.timestamp_millis());
+
+        let rows = statement.query_map(params![since, until], |row| {
+            let play = Play {
+                song_id: SongId(row.get(1)?),
+                title: row.get(2)?,
+                artist: row.get(3)?,
+                album: row.get(4)?,
+                genres: Vec::new(),
+                played_at: DateTime::from_timestamp_millis(row.get(5)?).unwrap_or_default(),
+                seconds: row.get(6)?,
+                stopped_early: row.get(7)?,
+            };
+
+            Ok((row.get::<_, i64>(0)?, play))
+        })?;
+
+        let mut plays = Vec::new();
+
+        for row in rows {
+            let (id, mut play) = row?;
+
+            play.genres = genres
+                .query_map([id], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            plays.push(play);
+        }
+
+        Ok(plays)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn song() -> Result<Song> {
+        Ok(serde_json::from_value(json!({
+            "id": "s-1",
+            "isDir": false,
+            "title": "Sinnerman",
+            "artist": "Nina Simone",
+            "album": "Pastel Blues",
+            "genres": [{"name": "Jazz"}, {"name": "Soul"}],
+            "duration": 620
+        }))?)
+    }
+
+    fn time(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_plays_stopped_early() -> Result<()> {
+        let song = song()?;
+        let played_at = time("2024-05-01T12:00:00Z");
+
+        let play = |seconds, finished| {
+            Play::new(&song, played_at, Duration::from_secs(seconds), finished).stopped_early
+        };
+
+        assert!(play(30, false));
+        assert!(!play(240, false));
+        assert!(!play(30, true));
+
+        let mut short = song.clone();
+        short.duration = Some(100);
+
+        assert!(Play::new(&short, played_at, Duration::from_secs(49), false).stopped_early);
+        assert!(!Play::new(&short, played_at, Duration::from_secs(50), false).stopped_early);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_and_read_plays() -> Result<()> {
+        let mut history = History::open_in_memory()?;
+        let song = song()?;
+
+        let first = Play::new(
+            &song,
+            time("2024-05-01T12:00:00Z"),
+            Duration::from_secs(620),
+            true,
+        );
+        let mut second = Play::new(
+            &song,
+            time("2024-06-01T12:00:00Z"),
+            Duration::from_secs(12),
+            false,
+        );
+        second.genres.clear();
+
+        history.record(&second)?;
+        history.record(&first)?;
+
+        assert_eq!(
+            history.plays(None, None)?,
+            vec![first.clone(), second.clone()]
+        );
+        assert_eq!(
+            history.plays(Some(time("2024-05-02T00:00:00Z")), None)?,
+            vec![second]
+        );
+        assert_eq!(
+            history.plays(None, Some(time("2024-06-01T12:00:00Z")))?,
+            vec![first]
+        );
+
+        Ok(())
+    }
+}
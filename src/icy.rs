@@ -0,0 +1,141 @@
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Strips SHOUTcast/Icecast metadata blocks from an audio stream.
+///
+/// After every `metaint` bytes of audio the server sends one length byte, followed
+/// by that many 16 byte blocks of metadata such as `StreamTitle='...';`.
+pub struct IcyReader<R: Read> {
+    inner: R,
+    metaint: Option<usize>,
+    until_metadata: usize,
+    titles: Sender<String>,
+}
+
+impl<R: Read> IcyReader<R> {
+    pub fn new(inner: R, metaint: Option<usize>) -> (IcyReader<R>, Receiver<String>) {
+        let (titles, receiver) = channel();
+
+        let reader = IcyReader {
+            inner,
+            metaint: metaint.filter(|metaint| *metaint > 0),
+            until_metadata: metaint.unwrap_or_default(),
+            titles,
+        };
+
+        (reader, receiver)
+    }
+
+    /// Returns false if the stream ended instead.
+    fn read_metadata(&mut self) -> std::io::Result<bool> {
+        let mut length = [0; 1];
+
+        if self.inner.read(&mut length)? == 0 {
+            return Ok(false);
+        }
+
+        let mut metadata = vec![0; usize::from(length[0]) * 16];
+        self.inner.read_exact(&mut metadata)?;
+
+        if let Some(title) = parse_stream_title(&String::from_utf8_lossy(&metadata)) {
+            // Nobody listening for titles is fine.
+            let _ = self.titles.send(title);
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(metaint) = self.metaint else {
+            return self.inner.read(buf);
+        };
+
+        if self.until_metadata == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+
+            self.until_metadata = metaint;
+        }
+
+        let len = buf.len().min(self.until_metadata);
+        let read = self.inner.read(&mut buf[..len])?;
+
+        self.until_metadata -= read;
+
+        Ok(read)
+    }
+}
+
+pub fn parse_stream_title(metadata: &str) -> Option<String> {
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &metadata[start..];
+    let end = rest.find("';").unwrap_or(rest.trim_end_matches('\0').len());
+
+    Some(rest[..end].trim().to_owned()).filter(|title| !title.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn metadata_block(text: &str) -> Vec<u8> {
+        let blocks = text.len().div_ceil(16);
+
+        let mut block = vec![blocks as u8];
+        block.extend(text.as_bytes());
+        block.resize(1 + blocks * 16, 0);
+
+        block
+    }
+
+    #[test]
+    fn test_parse_stream_title() {
+        assert_eq!(
+            parse_stream_title("StreamTitle='Miles Davis - So What';StreamUrl='';\0\0"),
+            Some("Miles Davis - So What".to_owned())
+        );
+        assert_eq!(
+            parse_stream_title("StreamTitle='It''s Late';"),
+            Some("It''s Late".to_owned())
+        );
+        assert_eq!(parse_stream_title("StreamTitle='';"), None);
+        assert_eq!(parse_stream_title("StreamUrl='';"), None);
+    }
+
+    #[test]
+    fn test_icy_reader_strips_metadata() -> std::io::Result<()> {
+        let mut data = b"abcd".to_vec();
+        data.extend(metadata_block("StreamTitle='First';"));
+        data.extend(b"efgh");
+        data.push(0);
+        data.extend(b"ijkl");
+
+        let (mut reader, titles) = IcyReader::new(Cursor::new(data), Some(4));
+
+        let mut audio = Vec::new();
+        reader.read_to_end(&mut audio)?;
+
+        assert_eq!(audio, b"abcdefghijkl");
+        assert_eq!(titles.try_iter().collect::<Vec<_>>(), vec!["First"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_icy_reader_without_metadata() -> std::io::Result<()> {
+        let (mut reader, titles) = IcyReader::new(Cursor::new(b"abcd".to_vec()), None);
+
+        let mut audio = Vec::new();
+        reader.read_to_end(&mut audio)?;
+
+        assert_eq!(audio, b"abcd");
+        assert!(titles.try_recv().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,746 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use toml::{Table, Value};
+
+use crate::config::{Config, SubsonicConfig};
+
+// Settings with a built-in default go here, so they show up as such in `config show`.
+const DEFAULT_CONFIG: &str = r#"
+[download]
+template = "{artist}/{year} - {album}/{disc}-{track} {title}.{suffix}"
+
+[player]
+bookmark_threshold = 1200
+"#;
+
+const ENVIRONMENT_PREFIX: &str = "KNUCKLES_";
+const ENVIRONMENT_SEPARATOR: &str = "__";
+
+const AUTH_KEYS: &[&str] = &[
+    "password",
+    "password_command",
+    "password_env",
+    "keyring",
+    "token",
+];
+const SECRET_KEYS: &[&str] = &["password", "hash"];
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Environment(String),
+    CommandLine(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Source::*;
+        match self {
+            Default => write!(fmt, "built-in default"),
+            File(path) => write!(fmt, "file {}", path.display()),
+            Environment(variable) => write!(fmt, "environment variable {variable}"),
+            CommandLine(flag) => write!(fmt, "command line {flag}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Layer {
+    pub source: Source,
+    pub table: Table,
+    lines: BTreeMap<String, usize>,
+}
+
+impl Layer {
+    pub fn redacted(&self) -> Vec<(String, String)> {
+        let mut values = Vec::new();
+        flatten("", &self.table, &mut values);
+
+        values
+            .into_iter()
+            .map(|(key, value)| {
+                let value = display_value(&key, value);
+                (key, value)
+            })
+            .collect()
+    }
+
+    pub fn line_of(&self, key: &str) -> Option<usize> {
+        self.lines.get(key).copied()
+    }
+
+    fn location_of(&self, key: &str) -> String {
+        match self.line_of(key) {
+            Some(line) => format!("{}, line {line}", self.source),
+            None => self.source.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResolvedValue {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigLayers {
+    layers: Vec<Layer>,
+}
+
+fn table_from_key(key: &str, value: Value) -> Result<Table> {
+    let mut parts = key.split('.').rev();
+
+    let Some(last) = parts.next().filter(|part| !part.is_empty()) else {
+        bail!("Invalid configuration key `{key}`.");
+    };
+
+    let mut table = Table::from_iter([(last.to_owned(), value)]);
+
+    for part in parts {
+        if part.is_empty() {
+            bail!("Invalid configuration key `{key}`.");
+        }
+
+        table = Table::from_iter([(part.to_owned(), Value::Table(table))]);
+    }
+
+    Ok(table)
+}
+
+// Values are read as TOML, so numbers and lists keep their type, and anything that is not
+// valid TOML, such as a bare URL, is taken as a string. Quote a value to keep it a string.
+fn parse_value(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_owned()))
+}
+
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+fn key_lines_in_table(
+    text: &str,
+    prefix: &str,
+    table: &dyn toml_edit::TableLike,
+    out: &mut BTreeMap<String, usize>,
+) {
+    for (key, item) in table.iter() {
+        let path = format!("{prefix}{key}");
+
+        if let Some(inner) = item.as_table_like() {
+            key_lines_in_table(text, &format!("{path}."), inner, out);
+        } else if let Some(span) = table.get_key_value(key).and_then(|(key, _)| key.span()) {
+            out.insert(path, line_at(text, span.start));
+        }
+    }
+}
+
+fn key_lines(text: &str) -> BTreeMap<String, usize> {
+    let mut lines = BTreeMap::new();
+
+    if let Ok(document) = toml_edit::ImDocument::parse(text) {
+        key_lines_in_table(text, "", document.as_table(), &mut lines);
+    }
+
+    lines
+}
+
+fn merge_into(
+    target: &mut Table,
+    origins: &mut BTreeMap<String, usize>,
+    prefix: &str,
+    table: &Table,
+    layer: usize,
+) {
+    if table.keys().any(|key| AUTH_KEYS.contains(&key.as_str())) {
+        for key in AUTH_KEYS.iter().filter(|key| !table.contains_key(**key)) {
+            if target.remove(*key).is_some() {
+                let path = format!("{prefix}{key}");
+                origins.retain(|origin, _| {
+                    origin != &path && !origin.starts_with(&format!("{path}."))
+                });
+            }
+        }
+    }
+
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+
+        match (target.get_mut(key), value) {
+            (Some(Value::Table(existing)), Value::Table(incoming)) => {
+                merge_into(existing, origins, &format!("{path}."), incoming, layer);
+            }
+            _ => {
+                origins.retain(|origin, _| !origin.starts_with(&format!("{path}.")));
+
+                if let Value::Table(incoming) = value {
+                    let mut fresh = Table::new();
+                    merge_into(&mut fresh, origins, &format!("{path}."), incoming, layer);
+                    target.insert(key.clone(), Value::Table(fresh));
+                } else {
+                    origins.insert(path, layer);
+                    target.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+fn flatten<'a>(prefix: &str, table: &'a Table, out: &mut Vec<(String, &'a Value)>) {
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+
+        match value {
+            Value::Table(inner) => flatten(&format!("{path}."), inner, out),
+            value => out.push((path, value)),
+        }
+    }
+}
+
+fn display_value(key: &str, value: &Value) -> String {
+    let leaf = key.rsplit('.').next().unwrap_or(key);
+
+    if SECRET_KEYS.contains(&leaf) {
+        Value::String(REDACTED.to_owned()).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn server_tables(table: &Table) -> Vec<(String, &Table)> {
+    let client = table
+        .get("client")
+        .and_then(Value::as_table)
+        .map(|client| ("client".to_owned(), client));
+
+    let servers = table
+        .get("servers")
+        .and_then(Value::as_table)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, server)| Some((format!("servers.{name}"), server.as_table()?)));
+
+    client.into_iter().chain(servers).collect()
+}
+
+impl ConfigLayers {
+    pub fn new() -> Result<Self> {
+        let mut layers = Self::default();
+
+        layers.add_layer(
+            Source::Default,
+            toml::from_str(DEFAULT_CONFIG).context("Invalid built-in configuration.")?,
+        );
+
+        Ok(layers)
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn add_layer(&mut self, source: Source, table: Table) {
+        self.layers.push(Layer {
+            source,
+            table,
+            lines: BTreeMap::new(),
+        });
+    }
+
+    pub fn add_text(&mut self, source: Source, text: &str) -> Result<()> {
+        let table = toml::from_str(text).with_context(|| format!("Could not parse {source}."))?;
+
+        self.layers.push(Layer {
+            source,
+            table,
+            lines: key_lines(text),
+        });
+
+        Ok(())
+    }
+
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read configuration file {}.", path.display()))?;
+
+        self.add_text(Source::File(path.to_owned()), &text)
+    }
+
+    pub fn add_file_if_exists(&mut self, path: &Path) -> Result<()> {
+        if path.exists() {
+            self.add_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_environment(
+        &mut self,
+        variables: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<()> {
+        let mut variables: Vec<_> = variables
+            .into_iter()
+            .filter_map(|(variable, value)| {
+                let key = variable
+                    .strip_prefix(ENVIRONMENT_PREFIX)?
+                    .to_lowercase()
+                    .replace(ENVIRONMENT_SEPARATOR, ".");
+
+                let top_level = key.split('.').next().unwrap_or_default();
+
+                Config::KEYS
+                    .contains(&top_level)
+                    .then_some((variable, key, value))
+            })
+            .collect();
+
+        variables.sort();
+
+        for (variable, key, value) in variables {
+            let table = table_from_key(&key, parse_value(&value))?;
+            self.add_layer(Source::Environment(variable), table);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_override(&mut self, flag: &str, key: &str, value: &str) -> Result<()> {
+        let table = table_from_key(key, parse_value(value))?;
+
+        self.add_layer(Source::CommandLine(flag.to_owned()), table);
+
+        Ok(())
+    }
+
+    fn merged(&self) -> (Table, BTreeMap<String, usize>) {
+        let mut table = Table::new();
+        let mut origins = BTreeMap::new();
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            merge_into(&mut table, &mut origins, "", &layer.table, index);
+        }
+
+        (table, origins)
+    }
+
+    fn check_servers(&self, table: &Table, origins: &BTreeMap<String, usize>) -> Result<()> {
+        for (prefix, server) in server_tables(table) {
+            if let Err((field, error)) = SubsonicConfig::check_fields(server) {
+                let key = format!("{prefix}.{field}");
+
+                match origins.get(&key) {
+                    Some(&index) => bail!(
+                        "Invalid value for `{key}` ({}): {}",
+                        self.layers[index].location_of(&key),
+                        error.message()
+                    ),
+                    None => bail!("Invalid value for `{key}`: {}", error.message()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        let (table, origins) = self.merged();
+
+        self.check_servers(&table, &origins)?;
+
+        Ok(Value::Table(table).try_into()?)
+    }
+
+    pub fn resolved(&self) -> Vec<ResolvedValue> {
+        let (table, mut origins) = self.merged();
+
+        let mut values = Vec::new();
+        flatten("", &table, &mut values);
+
+        values
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let layer = &self.layers[origins.remove(&key)?];
+
+                Some(ResolvedValue {
+                    value: display_value(&key, value),
+                    source: layer.source.clone(),
+                    line: layer.line_of(&key),
+                    key,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use crate::{
+        config::{AuthInfo, SubsonicConfig},
+        test_util::test_data_path,
+        types::{ServerUrl, Username},
+    };
+
+    use super::*;
+
+    fn read_server(text: &str) -> Result<SubsonicConfig> {
+        layers_from(&[("knuckles.toml", text)])?
+            .config()?
+            .client
+            .context("Missing client section.")
+    }
+
+    fn layers_from(texts: &[(&str, &str)]) -> Result<ConfigLayers> {
+        let mut layers = ConfigLayers::new()?;
+
+        for (name, text) in texts {
+            layers.add_text(Source::File(PathBuf::from(name)), &dedent(text))?;
+        }
+
+        Ok(layers)
+    }
+
+    #[test]
+    fn test_later_layers_take_precedence() -> Result<()> {
+        let mut layers = layers_from(&[
+            (
+                "/etc/knuckles/knuckles.toml",
+                r#"
+                [client]
+                url = "https://system.example.com"
+                username = "system"
+                password = "password"
+            "#,
+            ),
+            (
+                "/home/test/.config/knuckles.toml",
+                r#"
+                [client]
+                username = "test"
+            "#,
+            ),
+        ])?;
+
+        layers.add_environment([(
+            "KNUCKLES_CLIENT__URL".to_owned(),
+            "https://env.example.com".to_owned(),
+        )])?;
+
+        let config = layers.config()?;
+        let server = config.server(None)?;
+        assert_eq!(
+            layers.resolved(),
+            vec![
+                ResolvedValue {
+                    key: "client.password".to_owned(),
+                    value: "\"<redacted>\"".to_owned(),
+                    source: Source::File(PathBuf::from("/etc/knuckles/knuckles.toml")),
+                    line: Some(5),
+                },
+                ResolvedValue {
+                    key: "client.url".to_owned(),
+                    value: "\"https://env.example.com\"".to_owned(),
+                    source: Source::Environment("KNUCKLES_CLIENT__URL".to_owned()),
+                    line: None,
+                },
+                ResolvedValue {
+                    key: "client.username".to_owned(),
+                    value: "\"test\"".to_owned(),
+                    source: Source::File(PathBuf::from("/home/test/.config/knuckles.toml")),
+                    line: Some(3),
+                },
+                ResolvedValue {
+                    key: "download.template".to_owned(),
+                    value: "\"{artist}/{year} - {album}/{disc}-{track} {title}.{suffix}\""
+                        .to_owned(),
+                    source: Source::Default,
+                    line: None,
+                },
+                ResolvedValue {
+                    key: "player.bookmark_threshold".to_owned(),
+                    value: "1200".to_owned(),
+                    source: Source::Default,
+                    line: None,
+                },
+            ]
+        );
+
+        assert_eq!(
+            server,
+            &read_server(
+                r#"
+                [client]
+                url = "https://env.example.com"
+                username = "test"
+                password = "password"
+            "#
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth_method_is_replaced_as_a_whole() -> Result<()> {
+        let mut layers = layers_from(&[(
+            "knuckles.toml",
+            r#"
+            [client]
+            url = "https://example.com"
+            username = "test"
+
+            [client.token]
+            hash = "a1b2c3"
+            salt = "abcde"
+        "#,
+        )])?;
+
+        layers.add_override("--set", "client.password_command", "pass show music")?;
+
+        let keys: Vec<_> = layers
+            .resolved()
+            .into_iter()
+            .map(|resolved| resolved.key)
+            .collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                "client.password_command",
+                "client.url",
+                "client.username",
+                "download.template",
+                "player.bookmark_threshold"
+            ]
+        );
+
+        let config = layers.config()?;
+
+        assert_eq!(
+            config.server(None)?,
+            &read_server(
+                r#"
+                [client]
+                url = "https://example.com"
+                username = "test"
+                password_command = "pass show music"
+            "#
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment_variables() -> Result<()> {
+        let mut layers = ConfigLayers::new()?;
+
+        layers.add_environment([
+            (
+                "KNUCKLES_SERVERS__HOME__URL".to_owned(),
+                "https://home.example.com".to_owned(),
+            ),
+            ("KNUCKLES_DEFAULT_SERVER".to_owned(), "home".to_owned()),
+            (
+                "KNUCKLES_SERVERS__HOME__USERNAME".to_owned(),
+                "test".to_owned(),
+            ),
+            (
+                "KNUCKLES_SERVERS__HOME__PASSWORD_ENV".to_owned(),
+                "KNUCKLES_PASSWORD".to_owned(),
+            ),
+            ("KNUCKLES_PASSWORD".to_owned(), "not a setting".to_owned()),
+            ("HOME".to_owned(), "/home/test".to_owned()),
+        ])?;
+
+        let sources: Vec<_> = layers
+            .layers()
+            .iter()
+            .map(|layer| layer.source.clone())
+            .collect();
+
+        assert_eq!(
+            sources,
+            vec![
+                Source::Default,
+                Source::Environment("KNUCKLES_DEFAULT_SERVER".to_owned()),
+                Source::Environment("KNUCKLES_SERVERS__HOME__PASSWORD_ENV".to_owned()),
+                Source::Environment("KNUCKLES_SERVERS__HOME__URL".to_owned()),
+                Source::Environment("KNUCKLES_SERVERS__HOME__USERNAME".to_owned()),
+            ]
+        );
+
+        let config = layers.config()?;
+
+        assert_eq!(
+            config.server(None)?,
+            &SubsonicConfig {
+                url: ServerUrl::unchecked("https://home.example.com/"),
+                username: Username::unchecked("test"),
+                auth_info: AuthInfo::PasswordEnv("KNUCKLES_PASSWORD".to_owned()),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_settings_from_the_environment() -> Result<()> {
+        let mut layers = ConfigLayers::new()?;
+
+        layers.add_text(
+            Source::File(PathBuf::from("/home/test/.config/knuckles.toml")),
+            &dedent(
+                r#"
+                [player]
+                bookmark_threshold = 900
+            "#,
+            ),
+        )?;
+        layers.add_environment([(
+            "KNUCKLES_PLAYER__BOOKMARK_THRESHOLD".to_owned(),
+            "3600".to_owned(),
+        )])?;
+
+        assert_eq!(layers.config()?.player.bookmark_threshold, 3600);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_values_point_at_their_origin() -> Result<()> {
+        let mut layers = layers_from(&[(
+            "/home/test/.config/knuckles.toml",
+            r#"
+            default_server = "home"
+
+            [servers.home]
+            url = "dummyurl"
+            username = "test"
+            password = "password"
+        "#,
+        )])?;
+
+        match layers.config() {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Invalid value for `servers.home.url` (file /home/test/.config/knuckles.toml, line 5): `dummyurl` is not a valid URL (relative URL without a base)."
+            ),
+        }
+
+        layers.add_environment([
+            (
+                "KNUCKLES_SERVERS__HOME__URL".to_owned(),
+                "https://home.example.com".to_owned(),
+            ),
+            ("KNUCKLES_SERVERS__HOME__USERNAME".to_owned(), "".to_owned()),
+        ])?;
+
+        match layers.config() {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Invalid value for `servers.home.username` (environment variable KNUCKLES_SERVERS__HOME__USERNAME): Username must not be empty."
+            ),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_override_values_keep_their_type() -> Result<()> {
+        let mut layers = ConfigLayers::new()?;
+
+        layers.add_override("--set", "player.bookmark_threshold", "600")?;
+        layers.add_override("--set", "client.url", "https://example.com/music")?;
+        layers.add_override("--set", "client.username", "\"1234\"")?;
+        layers.add_override("--set", "client.password", "hunter2")?;
+
+        let config = layers.config()?;
+
+        assert_eq!(config.player.bookmark_threshold, 600);
+        assert_eq!(
+            config.server(None)?.url,
+            ServerUrl::unchecked("https://example.com/music/")
+        );
+        assert_eq!(config.server(None)?.username, Username::unchecked("1234"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smart_playlist_rules_override() -> Result<()> {
+        let mut layers = ConfigLayers::new()?;
+
+        layers.add_environment([(
+            "KNUCKLES_SMART_PLAYLISTS__COUNTRY__RULES".to_owned(),
+            r#"["genre = Folk, World, & Country", "year < 1980"]"#.to_owned(),
+        )])?;
+
+        assert_eq!(
+            layers.config()?.smart_playlists["country"].description(),
+            "genre = Folk, World, & Country, year < 1980"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_override_key() -> Result<()> {
+        let mut layers = ConfigLayers::new()?;
+
+        match layers.add_override("--set", "client..url", "x") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(e.to_string(), "Invalid configuration key `client..url`."),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_file() -> Result<()> {
+        let config_path = test_data_path("test_read_config_from_path/knuckles-token.toml");
+
+        let mut layers = ConfigLayers::new()?;
+        layers.add_file(&config_path)?;
+        layers.add_file_if_exists(Path::new("/nonexistent/knuckles.toml"))?;
+
+        let redacted = layers.layers()[1].redacted();
+
+        assert_eq!(
+            redacted,
+            vec![
+                ("client.token.hash".to_owned(), "\"<redacted>\"".to_owned()),
+                ("client.token.salt".to_owned(), "\"abcde\"".to_owned()),
+                (
+                    "client.url".to_owned(),
+                    "\"https://subsonic.example.com\"".to_owned()
+                ),
+                ("client.username".to_owned(), "\"test\"".to_owned()),
+            ]
+        );
+
+        let config = layers.config()?;
+
+        assert_eq!(
+            config.server(None)?.url,
+            ServerUrl::unchecked("https://subsonic.example.com/")
+        );
+
+        Ok(())
+    }
+}
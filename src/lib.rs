@@ -0,0 +1,26 @@
+pub mod api_types;
+pub mod client;
+pub mod config;
+pub mod download;
+pub mod error;
+pub mod hash;
+pub mod history;
+pub mod icy;
+pub mod layers;
+pub mod lyrics;
+mod macros;
+pub mod password;
+pub mod player;
+pub mod playlist;
+pub mod radio;
+pub mod search;
+pub mod smart;
+pub mod snapshot;
+pub mod stats;
+pub mod stream;
+pub mod strong;
+pub mod sync;
+#[cfg(test)]
+mod test_util;
+pub mod token;
+pub mod types;
@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::api_types::{Song, StructuredLyrics};
+use crate::client::SubsonicClient;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Line {
+    /// Milliseconds from the start of the song, for synced lyrics.
+    pub start: Option<u64>,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lyrics {
+    pub synced: bool,
+    pub lines: Vec<Line>,
+}
+
+impl Lyrics {
+    /// Applies the offset to every line, as in LRC a positive offset shows lines sooner.
+    pub fn from_structured(lyrics: StructuredLyrics) -> Self {
+        let offset = lyrics.offset.unwrap_or_default();
+
+        let lines = lyrics
+            .line
+            .unwrap_or_default()
+            .into_iter()
+            .map(|line| Line {
+                start: line
+                    .start
+                    .map(|start| start.saturating_add_signed(offset.saturating_neg())),
+                text: line.value,
+            })
+            .collect();
+
+        Self {
+            synced: lyrics.synced,
+            lines,
+        }
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let lines = text
+            .lines()
+            .map(|line| Line {
+                start: None,
+                text: line.trim_end().to_owned(),
+            })
+            .collect();
+
+        Self {
+            synced: false,
+            lines,
+        }
+    }
+
+    /// The index of the line being sung at `position`, for synced lyrics.
+    pub fn line_at(&self, position: Duration) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+
+        let position = u64::try_from(position.as_millis()).unwrap_or(u64::MAX);
+
+        self.lines
+            .iter()
+            .rposition(|line| line.start.is_some_and(|start| start <= position))
+    }
+}
+
+/// Looks up lyrics by song ID, falling back to the classic artist and title lookup
+/// on servers without the OpenSubsonic songLyrics extension.
+pub async fn song_lyrics(client: &SubsonicClient, song: &Song) -> Result<Option<Lyrics>> {
+    if let Ok(mut structured) = client.lyrics_by_song_id(&song.id).await {
+        // Prefer synced lyrics, keeping the server's order otherwise.
+        structured.sort_by_key(|lyrics| !lyrics.synced);
+
+        if let Some(lyrics) = structured.into_iter().next() {
+            return Ok(Some(Lyrics::from_structured(lyrics)));
+        }
+    }
+
+    let classic = client
+        .lyrics(song.artist.as_deref(), Some(&song.title))
+        .await?;
+
+    Ok(classic
+        .value
+        .filter(|text| !text.trim().is_empty())
+        .map(|text| Lyrics::from_text(&text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn structured(offset: i64) -> Result<StructuredLyrics> {
+        Ok(serde_json::from_value(json!({
+            "lang": "eng",
+            "synced": true,
+            "offset": offset,
+            "line": [
+                { "start": 1000, "value": "First" },
+                { "start": 5000, "value": "Second" },
+                { "start": 9000, "value": "Third" }
+            ]
+        }))?)
+    }
+
+    #[test]
+    fn test_line_at() -> Result<()> {
+        let lyrics = Lyrics::from_structured(structured(0)?);
+
+        assert_eq!(lyrics.line_at(Duration::from_millis(500)), None);
+        assert_eq!(lyrics.line_at(Duration::from_millis(1000)), Some(0));
+        assert_eq!(lyrics.line_at(Duration::from_millis(8999)), Some(1));
+        assert_eq!(lyrics.line_at(Duration::from_secs(300)), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_shows_lines_sooner() -> Result<()> {
+        let lyrics = Lyrics::from_structured(structured(1500)?);
+
+        let starts: Vec<_> = lyrics.lines.iter().map(|line| line.start).collect();
+
+        assert_eq!(starts, vec![Some(0), Some(3500), Some(7500)]);
+
+        let lyrics = Lyrics::from_structured(structured(-500)?);
+
+        assert_eq!(lyrics.lines[0].start, Some(1500));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsynced_lyrics_have_no_current_line() {
+        let lyrics = Lyrics::from_text("First\r\nSecond\n");
+
+        assert_eq!(
+            lyrics.lines,
+            vec![
+                Line {
+                    start: None,
+                    text: "First".to_owned(),
+                },
+                Line {
+                    start: None,
+                    text: "Second".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(lyrics.line_at(Duration::from_secs(10)), None);
+    }
+}
@@ -1,11 +1,5 @@
 macro_rules! strong_alias {
-    ( $n:ident, $t:ident ) => {
-        strong_alias!( $n, $t, );
-    };
-    ( $n:ident, $t:ident, $($derivs:ident),* ) => {
-        #[derive(Clone, Deserialize, $($derivs),*)]
-        pub struct $n(pub $t);
-
+    ( @common $n:ident, $t:ident ) => {
         impl fmt::Display for $n {
             fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
                 fmt.write_str(&self.0.to_string())
@@ -29,6 +23,37 @@ macro_rules! strong_alias {
             }
         }
     };
+    ( $n:ident, $t:ident, check = $check:path ) => {
+        strong_alias!( $n, $t, check = $check, );
+    };
+    ( $n:ident, $t:ident, check = $check:path, $($derivs:ident),* ) => {
+        #[derive(Clone, $($derivs),*)]
+        pub struct $n(pub $t);
+
+        impl $n {
+            #[allow(dead_code)]
+            pub fn new(value: impl Into<$t>) -> Result<$n, $crate::error::ValidationError> {
+                $check(value.into()).map($n)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $n {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $n::new($t::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+            }
+        }
+
+        strong_alias!( @common $n, $t );
+    };
+    ( $n:ident, $t:ident ) => {
+        strong_alias!( $n, $t, );
+    };
+    ( $n:ident, $t:ident, $($derivs:ident),* ) => {
+        #[derive(Clone, Deserialize, $($derivs),*)]
+        pub struct $n(pub $t);
+
+        strong_alias!( @common $n, $t );
+    };
 }
 
 pub(crate) use strong_alias;
@@ -38,6 +63,7 @@ mod tests {
     use core::fmt;
     use serde::Deserialize;
 
+    use crate::error::ValidationError;
     use crate::strong::Strong;
 
     strong_alias!(MyString, String);
@@ -65,4 +91,43 @@ mod tests {
 
         assert_eq!(a.get(), 42);
     }
+
+    fn check_lowercase(value: String) -> Result<String, ValidationError> {
+        if value.chars().any(char::is_uppercase) {
+            return Err(ValidationError(format!("`{value}` is not lowercase.")));
+        }
+
+        Ok(value)
+    }
+
+    strong_alias!(MyLowercase, String, check = check_lowercase, Debug);
+
+    #[test]
+    fn test_my_lowercase() {
+        assert_eq!(
+            MyLowercase::new("abc").map(Strong::get),
+            Ok("abc".to_owned())
+        );
+        assert_eq!(
+            MyLowercase::new("Abc").map(Strong::get),
+            Err(ValidationError("`Abc` is not lowercase.".to_owned()))
+        );
+        assert_eq!(MyLowercase::unchecked("Abc").get(), "Abc");
+    }
+
+    #[test]
+    fn test_deserialize_my_lowercase() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            value: MyLowercase,
+        }
+
+        let wrapper: Wrapper = toml::from_str(r#"value = "abc""#).unwrap();
+        assert_eq!(wrapper.value.get(), "abc");
+
+        match toml::from_str::<Wrapper>(r#"value = "Abc""#) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(e.message(), "`Abc` is not lowercase."),
+        }
+    }
 }
@@ -1,57 +1,179 @@
-use std::io::BufReader;
+use std::time::Duration;
 
 use anyhow::Result;
+use clap::Parser;
 
-mod api_types;
-mod client;
-mod config;
-mod error;
-mod hash;
-mod macros;
-mod stream;
-mod strong;
-#[cfg(test)]
-mod test_util;
-mod token;
-mod types;
+use knuckles::client::{AlbumListType, RandomSongsFilter};
+use knuckles::config::make_client;
+use knuckles::hash::default_hasher;
+use knuckles::history::{default_history_path, History};
+use knuckles::player::Player;
+use knuckles::snapshot::{default_snapshot_path, Snapshot};
 
-use client::AlbumListType;
-use hash::default_hasher;
-use stream::{SongStream, SyncReader};
+mod cli;
 
-use crate::config::{default_config_file_path, make_client, read_config_from_path};
+use cli::config::{load_config_layers, show_config};
+use cli::{Cli, Command, ConfigCommand};
 
-fn play_stream(s: SongStream<SyncReader>) -> Result<()> {
-    let buffered = BufReader::new(s);
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    if let Some(Command::Init) = cli.command {
+        return cli::init::init(&cli).await;
+    }
+
+    let layers = load_config_layers(&cli)?;
+
+    if let Some(Command::Config(ConfigCommand::Show { resolved })) = cli.command {
+        show_config(&layers, resolved);
+
+        return Ok(());
+    }
+
+    let config = layers.config()?;
+    let server = config.server(None)?;
+
+    // The history is all the report needs, so it works without reaching the server.
+    if let Some(Command::Stats {
+        year,
+        since,
+        until,
+        period,
+        limit,
+        format,
+    }) = &cli.command
+    {
+        let history = History::open(&default_history_path(server)?)?;
+        let range = cli::stats::Range {
+            year: *year,
+            since: *since,
+            until: *until,
+        };
+
+        return cli::stats::stats(&history, &range, *period, *limit, *format);
+    }
+
+    let client = make_client(server, &mut default_hasher())?;
+
+    let mut snapshot = Snapshot::open(&default_snapshot_path(server)?)?;
+    let history = History::open(&default_history_path(server)?)?;
+
+    let mut player = Player::new(client);
+
+    player.set_local_files(snapshot.local_files()?);
+    player.set_history(history);
+
+    player.set_show_lyrics(cli.lyrics);
+    player.set_jukebox(cli.jukebox);
+
+    let bookmark_threshold = config.player.bookmark_threshold;
+    player.set_bookmark_threshold(
+        (bookmark_threshold > 0).then(|| Duration::from_secs(bookmark_threshold)),
+    );
+
+    match &cli.command {
+        Some(Command::Init | Command::Config(_) | Command::Stats { .. }) => {
+            unreachable!("handled before connecting to the server")
+        }
+        Some(Command::Download {
+            album,
+            artist,
+            playlist,
+            to,
+            template,
+        }) => {
+            let source = cli::download::Source {
+                album: album.as_deref(),
+                artist: artist.as_deref(),
+                playlist: playlist.as_deref(),
+            };
+            let template = template.as_deref().unwrap_or(&config.download.template);
+
+            cli::download::download(player.client(), &mut snapshot, &source, to, template).await
+        }
+        Some(Command::Find { query, limit }) => {
+            cli::find::find(&player, &mut snapshot, query, *limit).await
+        }
+        Some(Command::Genre { name }) => cli::genre::play_genre(&mut player, name.as_deref()).await,
+        Some(Command::Jukebox(command)) => cli::jukebox::jukebox(&player, command).await,
+        Some(Command::Library(command)) => {
+            cli::library::library(&mut player, &mut snapshot, command).await
+        }
+        Some(Command::Playlist(command)) => {
+            cli::playlist::playlist(&player, &snapshot, command).await
+        }
+        Some(Command::Podcast(command)) => cli::podcast::podcast(&mut player, command).await,
+        Some(Command::Share(command)) => cli::share::share(&player, command).await,
+        Some(Command::Smart(command)) => {
+            cli::smart::smart(&mut player, &config.smart_playlists, command).await
+        }
+        Some(Command::Station(command)) => cli::station::station(&player, command).await,
+        Some(Command::Sync {
+            to,
+            playlists,
+            starred,
+            album_lists,
+            album_list_size,
+            template,
+        }) => {
+            let selectors =
+                cli::sync::selectors(playlists, *starred, album_lists, *album_list_size);
+            let template = template.as_deref().unwrap_or(&config.download.template);
+
+            cli::sync::sync(player.client(), &mut snapshot, to, &selectors, template).await
+        }
+        Some(Command::User(command)) => cli::user::user(&player, command).await,
+        Some(Command::Radio {
+            artist,
+            genre,
+            from_year,
+            to_year,
+        }) => {
+            let filter = RandomSongsFilter {
+                genre: genre.clone(),
+                from_year: *from_year,
+                to_year: *to_year,
+                music_folder_id: None,
+            };
+
+            cli::radio::play_radio(&mut player, artist.as_deref(), filter).await
+        }
+        None => play(&mut player, &mut snapshot).await,
+    }
+}
 
-    let decoder = tokio::task::block_in_place(|| rodio::decoder::Decoder::new(buffered))?;
+async fn play(player: &mut Player, snapshot: &mut Snapshot) -> Result<()> {
+    match player.client().ping().await {
+        Ok(response) => {
+            dbg!(response);
+        }
+        Err(e) => {
+            let songs = snapshot.local_songs()?;
 
-    let (_stream, stream_handle) = rodio::OutputStream::try_default()?;
+            if songs.is_empty() {
+                return Err(e);
+            }
 
-    let sink = rodio::Sink::try_new(&stream_handle)?;
+            eprintln!("The server is unreachable, playing songs saved locally: {e}");
 
-    sink.append(decoder);
+            player.set_offline(true);
+            player.enqueue(songs);
 
-    sink.sleep_until_end();
+            return player.play().await;
+        }
+    }
 
-    Ok(())
-}
+    if cli::queue::offer_resume(player).await? {
+        return player.play().await;
+    }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let config_path = default_config_file_path()?;
-    let config = read_config_from_path(&config_path)?;
-    let client = make_client(&config, &mut default_hasher());
-
-    dbg!(client.ping().await?);
-
-    let albums = client
+    let albums = player
+        .client()
         .albums(AlbumListType::AlphabeticalByName, None, None, None)
         .await?;
-    let first_album = client.album(&albums[0].id).await?;
-    let first_song = client.stream(&first_album.song[0].id, Some(true)).await?;
-
-    let song_stream = stream::from_response(first_song);
+    let first_album = player.client().album(&albums[0].id).await?;
 
-    play_stream(song_stream)
+    player.enqueue(first_album.song.into_iter().take(1));
+    player.play().await
 }
@@ -0,0 +1,124 @@
+use std::env;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::types::Password;
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+pub fn password_from_command(command: &str) -> Result<Password> {
+    let output = shell_command(command)
+        .output()
+        .with_context(|| format!("Could not run password command `{command}`."))?;
+
+    if !output.status.success() {
+        let status = output.status;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        match stderr.trim() {
+            "" => bail!("Password command `{command}` failed ({status})."),
+            stderr => bail!("Password command `{command}` failed ({status}): {stderr}"),
+        }
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("Password command `{command}` did not output valid UTF-8."))?;
+
+    match stdout.lines().next() {
+        Some(line) if !line.is_empty() => Ok(Password(line.to_owned())),
+        _ => bail!("Password command `{command}` did not output a password."),
+    }
+}
+
+pub fn password_from_env(variable: &str) -> Result<Password> {
+    let password = env::var(variable).with_context(|| {
+        format!("Could not read password from environment variable {variable}.")
+    })?;
+
+    Ok(Password(password))
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn password_from_keyring(service: &str, user: &str) -> Result<Password> {
+    let password = keyring::Entry::new(service, user)
+        .and_then(|entry| entry.get_password())
+        .with_context(|| {
+            format!("Could not read password for {user} from keyring service {service}.")
+        })?;
+
+    Ok(Password(password))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Strong;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_password_from_command() -> Result<()> {
+        let password = password_from_command("printf 'secret\\nurl: example.com\\n'")?;
+
+        assert_eq!(password.get(), "secret");
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_password_from_failing_command() {
+        match password_from_command("echo 'no such entry' >&2; exit 3") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Password command `echo 'no such entry' >&2; exit 3` failed (exit status: 3): no such entry"
+            ),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_password_from_silent_command() {
+        match password_from_command("true") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Password command `true` did not output a password."
+            ),
+        }
+    }
+
+    #[test]
+    fn test_password_from_env() -> Result<()> {
+        env::set_var("KNUCKLES_TEST_PASSWORD_FROM_ENV", "secret");
+
+        let password = password_from_env("KNUCKLES_TEST_PASSWORD_FROM_ENV")?;
+
+        assert_eq!(password.get(), "secret");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_password_from_missing_env() {
+        match password_from_env("KNUCKLES_TEST_PASSWORD_FROM_MISSING_ENV") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Could not read password from environment variable KNUCKLES_TEST_PASSWORD_FROM_MISSING_ENV."
+            ),
+        }
+    }
+}
@@ -0,0 +1,524 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rodio::{Decoder, OutputStream, Sink, Source};
+use tokio::task::block_in_place;
+
+use crate::api_types::{InternetRadioStation, PlayQueue, Song};
+use crate::client::{JukeboxAction, SubsonicClient};
+use crate::history::{History, Play};
+use crate::lyrics::{song_lyrics, Lyrics};
+use crate::radio::Radio;
+use crate::stream;
+use crate::types::SongId;
+
+/// Ask the radio for more songs once the queue is shorter than this.
+const RADIO_LOW_WATER: usize = 2;
+
+/// How often the play queue is saved to the server while a song plays.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const JUKEBOX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+trait MediaSource: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> MediaSource for T {}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+pub struct Player {
+    client: SubsonicClient,
+    queue: VecDeque<Song>,
+    radio: Option<Radio>,
+    start_position: Option<Duration>,
+    bookmark_threshold: Option<Duration>,
+    show_lyrics: bool,
+    jukebox: bool,
+    local_files: HashMap<SongId, PathBuf>,
+    offline: bool,
+    history: Option<History>,
+}
+
+impl Player {
+    pub fn new(client: SubsonicClient) -> Self {
+        Self {
+            client,
+            queue: VecDeque::new(),
+            radio: None,
+            start_position: None,
+            bookmark_threshold: None,
+            show_lyrics: false,
+            jukebox: false,
+            local_files: HashMap::new(),
+            offline: false,
+            history: None,
+        }
+    }
+
+    pub fn client(&self) -> &SubsonicClient {
+        &self.client
+    }
+
+    pub fn queue(&self) -> &VecDeque<Song> {
+        &self.queue
+    }
+
+    pub fn enqueue(&mut self, songs: impl IntoIterator<Item = Song>) {
+        self.queue.extend(songs);
+    }
+
+    pub fn set_radio(&mut self, radio: Option<Radio>) {
+        self.radio = radio;
+    }
+
+    /// Songs at least this long resume from, and save, a bookmark on the server.
+    pub fn set_bookmark_threshold(&mut self, threshold: Option<Duration>) {
+        self.bookmark_threshold = threshold;
+    }
+
+    pub fn set_show_lyrics(&mut self, show_lyrics: bool) {
+        self.show_lyrics = show_lyrics;
+    }
+
+    /// Plays on the server's jukebox instead of the local audio output.
+    pub fn set_jukebox(&mut self, jukebox: bool) {
+        self.jukebox = jukebox;
+    }
+
+    /// Songs saved locally, played from their files instead of streamed.
+    pub fn set_local_files(&mut self, local_files: HashMap<SongId, PathBuf>) {
+        self.local_files = local_files;
+    }
+
+    /// Plays only local files, without asking the server for anything.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Records every song played locally, for listening statistics.
+    pub fn set_history(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    fn record_play(
+        &mut self,
+        song: &Song,
+        played_at: DateTime<Utc>,
+        heard: Duration,
+        finished: bool,
+    ) {
+        if let Some(history) = &mut self.history {
+            if let Err(e) = history.record(&Play::new(song, played_at, heard, finished)) {
+                eprintln!("Could not record playing {}: {e}", song.title);
+            }
+        }
+    }
+
+    fn local_file(&self, song: &Song) -> Option<&PathBuf> {
+        self.local_files.get(&song.id).filter(|path| path.is_file())
+    }
+
+    pub fn is_saved_locally(&self, song: &Song) -> bool {
+        self.local_file(song).is_some()
+    }
+
+    /// Queues a play queue saved on the server, starting at its current song and position.
+    pub fn resume(&mut self, play_queue: PlayQueue) {
+        let mut entries = play_queue.entry.unwrap_or_default();
+
+        let current = play_queue
+            .current
+            .and_then(|current| entries.iter().position(|song| song.id == current));
+
+        if let Some(index) = current {
+            entries.drain(..index);
+            self.start_position = play_queue.position.map(Duration::from_millis);
+        }
+
+        self.queue.extend(entries);
+    }
+
+    async fn save_play_queue(&self, current: &Song, position: Duration) {
+        if self.offline {
+            return;
+        }
+
+        let ids: Vec<_> = std::iter::once(current)
+            .chain(&self.queue)
+            .map(|song| song.id.clone())
+            .collect();
+
+        if let Err(e) = self
+            .client
+            .save_play_queue(&ids, Some(&current.id), Some(millis(position)))
+            .await
+        {
+            eprintln!("Could not save the play queue: {e}");
+        }
+    }
+
+    fn wants_bookmark(&self, song: &Song) -> bool {
+        match (self.bookmark_threshold, song.duration) {
+            (Some(threshold), Some(duration)) => Duration::from_secs(duration) >= threshold,
+            _ => false,
+        }
+    }
+
+    async fn bookmark_position(&self, song: &Song) -> Option<Duration> {
+        if self.offline {
+            return None;
+        }
+
+        match self.client.bookmarks().await {
+            Ok(bookmarks) => bookmarks
+                .into_iter()
+                .find(|bookmark| bookmark.entry.id == song.id)
+                .map(|bookmark| Duration::from_millis(bookmark.position)),
+            Err(e) => {
+                eprintln!("Could not load bookmarks: {e}");
+                None
+            }
+        }
+    }
+
+    async fn load_lyrics(&self, song: &Song) -> Option<Lyrics> {
+        if !self.show_lyrics || self.offline {
+            return None;
+        }
+
+        match song_lyrics(&self.client, song).await {
+            Ok(lyrics) => lyrics,
+            Err(e) => {
+                eprintln!("Could not load lyrics for {}: {e}", song.title);
+                None
+            }
+        }
+    }
+
+    async fn save_progress(&self, song: &Song, position: Duration) {
+        self.save_play_queue(song, position).await;
+
+        if self.wants_bookmark(song) && !self.offline {
+            if let Err(e) = self
+                .client
+                .create_bookmark(&song.id, millis(position), None)
+                .await
+            {
+                eprintln!("Could not save a bookmark for {}: {e}", song.title);
+            }
+        }
+    }
+
+    #[cfg(not(tarpaulin_include))]
+    pub async fn play(&mut self) -> Result<()> {
+        if self.jukebox {
+            return self.play_on_jukebox().await;
+        }
+
+        let (_stream, stream_handle) = OutputStream::try_default()?;
+
+        let sink = Sink::try_new(&stream_handle)?;
+
+        let mut current: Option<Song> = None;
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        loop {
+            if let Some(radio) = self.radio.as_ref().filter(|_| !self.offline) {
+                if self.queue.len() < RADIO_LOW_WATER {
+                    let songs = radio
+                        .next_batch(&self.client, current.as_ref(), &self.queue)
+                        .await;
+
+                    self.queue.extend(songs);
+                }
+            }
+
+            let Some(song) = self.queue.pop_front() else {
+                break;
+            };
+
+            if let Some(radio) = &mut self.radio {
+                radio.remember(&song.id);
+            }
+
+            let source: Box<dyn MediaSource> = match self.local_file(&song) {
+                Some(path) => Box::new(File::open(path)?),
+                None if self.offline => {
+                    eprintln!("Skipping {}, it is not saved locally.", song.title);
+                    continue;
+                }
+                None => {
+                    let response = self.client.stream(&song.id, Some(true)).await?;
+                    Box::new(stream::from_response(response))
+                }
+            };
+
+            let mut decoder = block_in_place(|| Decoder::new(BufReader::new(source)))?;
+
+            let bookmarked = self.wants_bookmark(&song);
+
+            let start = match self.start_position.take() {
+                Some(start) => Some(start),
+                None if bookmarked => self.bookmark_position(&song).await,
+                None => None,
+            };
+            let mut has_bookmark = bookmarked && start.is_some();
+            let start = start.unwrap_or_default();
+
+            // Formats that cannot seek are decoded up to the start instead.
+            let skip = if start.is_zero() || block_in_place(|| decoder.try_seek(start)).is_ok() {
+                Duration::ZERO
+            } else {
+                start
+            };
+
+            sink.append(decoder.skip_duration(skip));
+
+            let played_at = Utc::now();
+            let started = Instant::now();
+
+            let lyrics = self.load_lyrics(&song).await;
+            let mut shown_line = None;
+
+            if let Some(lyrics) = lyrics.as_ref().filter(|lyrics| !lyrics.synced) {
+                for line in &lyrics.lines {
+                    println!("{}", line.text);
+                }
+            }
+
+            let mut saved = Instant::now();
+
+            self.save_play_queue(&song, start).await;
+
+            while !sink.empty() {
+                tokio::select! {
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    _ = &mut ctrl_c => {
+                        self.save_progress(&song, start + started.elapsed()).await;
+                        self.record_play(&song, played_at, started.elapsed(), false);
+
+                        return Ok(());
+                    }
+                }
+
+                if let Some(lyrics) = &lyrics {
+                    let index = lyrics.line_at(start + started.elapsed());
+
+                    if index != shown_line {
+                        if let Some(index) = index {
+                            println!("{}", lyrics.lines[index].text);
+                        }
+
+                        shown_line = index;
+                    }
+                }
+
+                if saved.elapsed() >= SAVE_INTERVAL {
+                    self.save_progress(&song, start + started.elapsed()).await;
+                    saved = Instant::now();
+                    has_bookmark |= bookmarked;
+                }
+            }
+
+            self.record_play(&song, played_at, started.elapsed(), true);
+
+            // A finished song starts from the beginning next time.
+            if has_bookmark && !self.offline {
+                if let Err(e) = self.client.delete_bookmark(&song.id).await {
+                    eprintln!("Could not remove the bookmark for {}: {e}", song.title);
+                }
+            }
+
+            current = Some(song);
+        }
+
+        Ok(())
+    }
+
+    /// Hands the queue to the server's jukebox and follows it until it stops. Radio,
+    /// bookmarks, lyrics, play queue sync and listening history only apply to local
+    /// playback.
+    #[cfg(not(tarpaulin_include))]
+    async fn play_on_jukebox(&mut self) -> Result<()> {
+        let songs: Vec<Song> = self.queue.drain(..).collect();
+
+        if songs.is_empty() {
+            return Ok(());
+        }
+
+        let ids = songs.iter().map(|song| song.id.clone()).collect();
+
+        self.client
+            .jukebox_control(&JukeboxAction::Set(ids))
+            .await?;
+
+        let mut status = self.client.jukebox_control(&JukeboxAction::Start).await?;
+        let mut shown_index = None;
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        while status.playing {
+            if shown_index != Some(status.current_index) {
+                if let Some(song) = usize::try_from(status.current_index)
+                    .ok()
+                    .and_then(|index| songs.get(index))
+                {
+                    println!("Playing {} on the jukebox.", song.title);
+                }
+
+                shown_index = Some(status.current_index);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(JUKEBOX_POLL_INTERVAL) => {}
+                _ = &mut ctrl_c => {
+                    self.client.jukebox_control(&JukeboxAction::Stop).await?;
+
+                    return Ok(());
+                }
+            }
+
+            status = self.client.jukebox_control(&JukeboxAction::Status).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Plays an internet radio station until its stream ends or Ctrl-C is pressed,
+    /// printing the titles the station announces.
+    #[cfg(not(tarpaulin_include))]
+    pub async fn play_station(&self, station: &InternetRadioStation) -> Result<()> {
+        let (_stream, stream_handle) = OutputStream::try_default()?;
+
+        let sink = Sink::try_new(&stream_handle)?;
+
+        let response = reqwest::Client::new()
+            .get(&station.stream_url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let (live, titles) = stream::live_from_response(response);
+        let buffered = BufReader::new(live);
+
+        let decoder = block_in_place(|| Decoder::new(buffered))?;
+
+        sink.append(decoder);
+
+        println!("Playing {}.", station.name);
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        while !sink.empty() {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = &mut ctrl_c => return Ok(()),
+            }
+
+            for title in titles.try_iter() {
+                println!("{title}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::token::TokenInfo;
+    use crate::types::{PasswordHash, Salt, ServerUrl, SongId, Username};
+
+    use super::*;
+
+    fn player() -> Player {
+        Player::new(SubsonicClient {
+            url: ServerUrl::unchecked("https://subsonic.example.com/"),
+            username: Username::unchecked("user"),
+            token_info: TokenInfo {
+                hash: PasswordHash::unchecked("a1b2c3"),
+                salt: Salt::unchecked("abcde"),
+            },
+        })
+    }
+
+    fn play_queue(current: Option<&str>) -> Result<PlayQueue> {
+        Ok(serde_json::from_value(json!({
+            "changed": "2024-03-01T12:00:00Z",
+            "changedBy": "DSub",
+            "username": "user",
+            "current": current,
+            "position": 61500,
+            "entry": [
+                { "id": "a", "isDir": false, "title": "A" },
+                { "id": "b", "isDir": false, "title": "B" },
+                { "id": "c", "isDir": false, "title": "C" }
+            ]
+        }))?)
+    }
+
+    fn queued_ids(player: &Player) -> Vec<SongId> {
+        player.queue().iter().map(|song| song.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_resume_starts_at_current_song() -> Result<()> {
+        let mut player = player();
+
+        player.resume(play_queue(Some("b"))?);
+
+        assert_eq!(
+            queued_ids(&player),
+            vec![SongId::unchecked("b"), SongId::unchecked("c")]
+        );
+        assert_eq!(player.start_position, Some(Duration::from_millis(61500)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_songs_are_bookmarked() -> Result<()> {
+        let mut player = player();
+
+        let song = |duration: u64| -> Result<Song> {
+            Ok(serde_json::from_value(json!({
+                "id": "a", "isDir": false, "title": "A", "duration": duration
+            }))?)
+        };
+
+        assert!(!player.wants_bookmark(&song(3600)?));
+
+        player.set_bookmark_threshold(Some(Duration::from_secs(1200)));
+
+        assert!(!player.wants_bookmark(&song(1199)?));
+        assert!(player.wants_bookmark(&song(1200)?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_without_current_song() -> Result<()> {
+        let mut player = player();
+
+        player.resume(play_queue(None)?);
+
+        assert_eq!(queued_ids(&player).len(), 3);
+        assert_eq!(player.start_position, None);
+
+        Ok(())
+    }
+}
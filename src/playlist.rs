@@ -0,0 +1,763 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::api_types::{PlaylistWithSongs, Song};
+use crate::client::SubsonicClient;
+use crate::search::{fold, fuzzy_score};
+use crate::types::SongId;
+
+const XSPF_NAMESPACE: &str = "http://xspf.org/ns/0/";
+const MUSIC_BRAINZ_RECORDING: &str = "https://musicbrainz.org/recording/";
+
+/// Search matches whose duration differs by more than this many seconds are other versions.
+const DURATION_TOLERANCE: u64 = 10;
+const SEARCH_COUNT: u64 = 20;
+
+/// Escaped in XSPF and JSPF locations, which are URIs rather than paths.
+const URI_ESCAPED: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+fn location_to_uri(location: &str) -> String {
+    utf8_percent_encode(location, URI_ESCAPED).to_string()
+}
+
+fn location_from_uri(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    percent_decode_str(path).decode_utf8_lossy().into_owned()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+    Jspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            extension if extension.eq_ignore_ascii_case("m3u") => Some(PlaylistFormat::M3u8),
+            extension => PlaylistFormat::from_str(extension, true).ok(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    /// A path, or a URL, decoded from the URI that XSPF and JSPF playlists hold.
+    pub location: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Seconds.
+    pub duration: Option<u64>,
+    pub music_brainz_id: Option<String>,
+}
+
+impl PlaylistEntry {
+    pub fn from_song(song: &Song) -> Self {
+        Self {
+            location: song.path.clone(),
+            title: Some(song.title.clone()),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            duration: song.duration,
+            music_brainz_id: song.music_brainz_id.clone(),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistEntry {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.artist, &self.title, &self.location) {
+            (Some(artist), Some(title), _) => write!(fmt, "{artist} - {title}"),
+            (None, Some(title), _) => fmt.write_str(title),
+            (_, None, Some(location)) => fmt.write_str(location),
+            (_, None, None) => fmt.write_str("(empty entry)"),
+        }
+    }
+}
+
+/// A playlist as read from or written to a file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PlaylistFile {
+    pub title: Option<String>,
+    pub annotation: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Jspf {
+    playlist: JspfPlaylist,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JspfPlaylist {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotation: Option<String>,
+    #[serde(default)]
+    track: Vec<JspfTrack>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JspfTrack {
+    #[serde(default, deserialize_with = "one_or_many")]
+    location: Vec<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    identifier: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    album: Option<String>,
+    /// Milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+}
+
+// JSPF has arrays of locations and identifiers, though some players write one string.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+fn music_brainz_id(identifier: &str) -> Option<String> {
+    let (_, id) = identifier.split_once("musicbrainz.org/recording/")?;
+
+    Some(id.trim_end_matches('/').to_owned()).filter(|id| !id.is_empty())
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl PlaylistFile {
+    pub fn from_playlist(playlist: &PlaylistWithSongs) -> Self {
+        Self {
+            title: Some(playlist.playlist_data.name.clone()),
+            annotation: playlist.playlist_data.comment.clone(),
+            entries: playlist
+                .entry
+                .iter()
+                .flatten()
+                .map(PlaylistEntry::from_song)
+                .collect(),
+        }
+    }
+
+    pub fn parse(format: PlaylistFormat, text: &str) -> Result<Self> {
+        match format {
+            PlaylistFormat::M3u8 => Ok(Self::parse_m3u8(text)),
+            PlaylistFormat::Xspf => Self::parse_xspf(text),
+            PlaylistFormat::Jspf => Self::parse_jspf(text),
+        }
+    }
+
+    /// Entries without a location are left out of M3U8 playlists, which only list files.
+    pub fn write(&self, format: PlaylistFormat) -> Result<String> {
+        match format {
+            PlaylistFormat::M3u8 => Ok(self.write_m3u8()),
+            PlaylistFormat::Xspf => Ok(self.write_xspf()),
+            PlaylistFormat::Jspf => self.write_jspf(),
+        }
+    }
+
+    fn parse_m3u8(text: &str) -> Self {
+        let mut playlist = Self::default();
+        let mut info = PlaylistEntry::default();
+
+        for line in text.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+                playlist.title = Some(name.trim().to_owned());
+            } else if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+                let (duration, name) = extinf.split_once(',').unwrap_or((extinf, ""));
+
+                // Durations may have decimals, with -1 for unknown.
+                info.duration = duration
+                    .split_whitespace()
+                    .next()
+                    .and_then(|duration| duration.parse::<f64>().ok())
+                    .filter(|duration| *duration >= 0.0)
+                    .map(|duration| duration.round() as u64);
+
+                match name.split_once(" - ") {
+                    Some((artist, title)) => {
+                        info.artist = Some(artist.trim().to_owned());
+                        info.title = Some(title.trim().to_owned());
+                    }
+                    None => {
+                        info.title = Some(name.trim().to_owned()).filter(|name| !name.is_empty());
+                    }
+                }
+            } else if !line.is_empty() && !line.starts_with('#') {
+                let mut entry = std::mem::take(&mut info);
+                entry.location = Some(line.to_owned());
+
+                playlist.entries.push(entry);
+            }
+        }
+
+        playlist
+    }
+
+    fn write_m3u8(&self) -> String {
+        let mut text = String::from("#EXTM3U\n");
+
+        if let Some(title) = &self.title {
+            text.push_str(&format!("#PLAYLIST:{title}\n"));
+        }
+
+        for entry in &self.entries {
+            let Some(location) = &entry.location else {
+                continue;
+            };
+
+            let duration = entry
+                .duration
+                .map_or_else(|| "-1".to_owned(), |duration| duration.to_string());
+            let name = match (&entry.artist, &entry.title) {
+                (Some(artist), Some(title)) => format!("{artist} - {title}"),
+                (None, Some(title)) => title.clone(),
+                (_, None) => String::new(),
+            };
+
+            text.push_str(&format!("#EXTINF:{duration},{name}\n{location}\n"));
+        }
+
+        text
+    }
+
+    fn parse_xspf(text: &str) -> Result<Self> {
+        let document = roxmltree::Document::parse(text).context("Invalid XSPF playlist.")?;
+
+        let text_of = |node: roxmltree::Node, name: &str| {
+            node.children()
+                .find(|child| child.has_tag_name(name))
+                .and_then(|child| child.text())
+                .map(|text| text.trim().to_owned())
+        };
+
+        let root = document.root_element();
+
+        let entries = root
+            .children()
+            .filter(|node| node.has_tag_name("trackList"))
+            .flat_map(|list| list.children().filter(|node| node.has_tag_name("track")))
+            .map(|track| PlaylistEntry {
+                location: text_of(track, "location").as_deref().map(location_from_uri),
+                title: text_of(track, "title"),
+                artist: text_of(track, "creator"),
+                album: text_of(track, "album"),
+                duration: text_of(track, "duration")
+                    .and_then(|duration| duration.parse::<u64>().ok())
+                    .map(|millis| (millis + 500) / 1000),
+                music_brainz_id: track
+                    .children()
+                    .filter(|node| node.has_tag_name("identifier"))
+                    .find_map(|node| music_brainz_id(node.text()?)),
+            })
+            .collect();
+
+        Ok(Self {
+            title: text_of(root, "title"),
+            annotation: text_of(root, "annotation"),
+            entries,
+        })
+    }
+
+    fn write_xspf(&self) -> String {
+        let element = |indent: &str, name: &str, value: &Option<String>| {
+            value.as_ref().map_or_else(String::new, |value| {
+                format!("{indent}<{name}>{}</{name}>\n", escape_xml(value))
+            })
+        };
+
+        let mut text = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <playlist version=\"1\" xmlns=\"{XSPF_NAMESPACE}\">\n"
+        );
+
+        text.push_str(&element("  ", "title", &self.title));
+        text.push_str(&element("  ", "annotation", &self.annotation));
+        text.push_str("  <trackList>\n");
+
+        for entry in &self.entries {
+            let identifier = entry
+                .music_brainz_id
+                .as_ref()
+                .map(|id| format!("{MUSIC_BRAINZ_RECORDING}{id}"));
+            let duration = entry.duration.map(|duration| (duration * 1000).to_string());
+            let location = entry.location.as_deref().map(location_to_uri);
+
+            text.push_str("    <track>\n");
+            text.push_str(&element("      ", "location", &location));
+            text.push_str(&element("      ", "identifier", &identifier));
+            text.push_str(&element("      ", "title", &entry.title));
+            text.push_str(&element("      ", "creator", &entry.artist));
+            text.push_str(&element("      ", "album", &entry.album));
+            text.push_str(&element("      ", "duration", &duration));
+            text.push_str("    </track>\n");
+        }
+
+        text.push_str("  </trackList>\n</playlist>\n");
+
+        text
+    }
+
+    fn parse_jspf(text: &str) -> Result<Self> {
+        let jspf: Jspf = serde_json::from_str(text).context("Invalid JSPF playlist.")?;
+
+        let entries = jspf
+            .playlist
+            .track
+            .into_iter()
+            .map(|track| PlaylistEntry {
+                location: track.location.first().map(|uri| location_from_uri(uri)),
+                title: track.title,
+                artist: track.creator,
+                album: track.album,
+                duration: track.duration.map(|millis| (millis + 500) / 1000),
+                music_brainz_id: track
+                    .identifier
+                    .iter()
+                    .find_map(|identifier| music_brainz_id(identifier)),
+            })
+            .collect();
+
+        Ok(Self {
+            title: jspf.playlist.title,
+            annotation: jspf.playlist.annotation,
+            entries,
+        })
+    }
+
+    fn write_jspf(&self) -> Result<String> {
+        let track = self
+            .entries
+            .iter()
+            .map(|entry| JspfTrack {
+                location: entry
+                    .location
+                    .as_deref()
+                    .map(location_to_uri)
+                    .into_iter()
+                    .collect(),
+                identifier: entry
+                    .music_brainz_id
+                    .iter()
+                    .map(|id| format!("{MUSIC_BRAINZ_RECORDING}{id}"))
+                    .collect(),
+                title: entry.title.clone(),
+                creator: entry.artist.clone(),
+                album: entry.album.clone(),
+                duration: entry.duration.map(|duration| duration * 1000),
+            })
+            .collect();
+
+        let jspf = Jspf {
+            playlist: JspfPlaylist {
+                title: self.title.clone(),
+                annotation: self.annotation.clone(),
+                track,
+            },
+        };
+
+        Ok(serde_json::to_string_pretty(&jspf)? + "\n")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchedBy {
+    MusicBrainzId,
+    Path,
+    Search,
+}
+
+#[derive(Debug, Default)]
+pub struct Matches {
+    pub songs: Vec<(SongId, MatchedBy)>,
+    pub unmatched: Vec<PlaylistEntry>,
+}
+
+/// Finds the songs of a library by path and MusicBrainz ID.
+pub struct SongMatcher<'a> {
+    by_path: HashMap<&'a str, &'a Song>,
+    by_music_brainz_id: HashMap<&'a str, &'a Song>,
+}
+
+impl<'a> SongMatcher<'a> {
+    pub fn new(library: &'a [Song]) -> Self {
+        let by_path = library
+            .iter()
+            .filter_map(|song| Some((song.path.as_deref()?, song)))
+            .collect();
+        let by_music_brainz_id = library
+            .iter()
+            .filter_map(|song| Some((song.music_brainz_id.as_deref()?, song)))
+            .collect();
+
+        Self {
+            by_path,
+            by_music_brainz_id,
+        }
+    }
+
+    /// Matches locations that end in a song's path, such as the same file under another
+    /// music directory.
+    pub fn by_path(&self, location: &str) -> Option<&'a Song> {
+        let location = match location.strip_prefix("file://") {
+            Some(path) => percent_decode_str(path).decode_utf8_lossy(),
+            None => Cow::Borrowed(location),
+        };
+        let location = location.replace('\\', "/");
+
+        let mut rest = location.as_str();
+
+        loop {
+            if let Some(song) = self.by_path.get(rest) {
+                return Some(song);
+            }
+
+            rest = rest.split_once('/')?.1;
+        }
+    }
+
+    pub fn by_music_brainz_id(&self, id: &str) -> Option<&'a Song> {
+        self.by_music_brainz_id.get(id).copied()
+    }
+}
+
+fn similarity(a: &str, b: &str) -> Option<u32> {
+    let (a, b) = (fold(a), fold(b));
+
+    fuzzy_score(&a, &b).max(fuzzy_score(&b, &a))
+}
+
+/// Picks the search result closest to the entry's title, artist and duration.
+pub fn best_match<'a>(entry: &PlaylistEntry, candidates: &'a [Song]) -> Option<&'a Song> {
+    let title = entry.title.as_deref()?;
+
+    candidates
+        .iter()
+        .filter(|song| match (entry.duration, song.duration) {
+            (Some(wanted), Some(duration)) => wanted.abs_diff(duration) <= DURATION_TOLERANCE,
+            _ => true,
+        })
+        .filter_map(|song| {
+            let title_score = similarity(title, &song.title)?;
+
+            let artist_score = match (&entry.artist, &song.artist) {
+                (Some(wanted), Some(artist)) => similarity(wanted, artist)?,
+                _ => 0,
+            };
+
+            Some((title_score + artist_score, song))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, song)| song)
+}
+
+async fn search_match(client: &SubsonicClient, entry: &PlaylistEntry) -> Result<Option<Song>> {
+    let Some(title) = &entry.title else {
+        return Ok(None);
+    };
+
+    let mut queries = vec![title.clone()];
+
+    if let Some(artist) = &entry.artist {
+        queries.insert(0, format!("{artist} {title}"));
+    }
+
+    for query in queries {
+        let candidates = client.search_songs(&query, Some(SEARCH_COUNT)).await?;
+
+        if let Some(song) = best_match(entry, &candidates) {
+            return Ok(Some(song.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Matches entries to songs in `library` by MusicBrainz ID, then by path, falling back
+/// to searching the server for the artist and title.
+pub async fn match_entries(
+    client: &SubsonicClient,
+    library: &[Song],
+    entries: Vec<PlaylistEntry>,
+) -> Result<Matches> {
+    let matcher = SongMatcher::new(library);
+    let mut matches = Matches::default();
+
+    for entry in entries {
+        let local = entry
+            .music_brainz_id
+            .as_deref()
+            .and_then(|id| matcher.by_music_brainz_id(id))
+            .map(|song| (song.id.clone(), MatchedBy::MusicBrainzId))
+            .or_else(|| {
+                let song = matcher.by_path(entry.location.as_deref()?)?;
+
+                Some((song.id.clone(), MatchedBy::Path))
+            });
+
+        let found = match local {
+            Some(found) => Some(found),
+            None => search_match(client, &entry)
+                .await?
+                .map(|song| (song.id, MatchedBy::Search)),
+        };
+
+        match found {
+            Some(found) => matches.songs.push(found),
+            None => matches.unmatched.push(entry),
+        }
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::types::Strong;
+
+    use super::*;
+
+    fn song(id: &str, title: &str, artist: &str, path: &str) -> Result<Song> {
+        Ok(serde_json::from_value(json!({
+            "id": id,
+            "isDir": false,
+            "title": title,
+            "artist": artist,
+            "album": "Blue",
+            "duration": 215,
+            "path": path,
+            "musicBrainzId": format!("mb-{id}")
+        }))?)
+    }
+
+    fn playlist() -> Result<PlaylistFile> {
+        Ok(PlaylistFile {
+            title: Some("Joni & friends".to_owned()),
+            annotation: Some("For <Sunday> mornings".to_owned()),
+            entries: vec![
+                PlaylistEntry::from_song(&song(
+                    "s-1",
+                    "A Case of You",
+                    "Joni Mitchell",
+                    "Joni Mitchell/Blue/08 A Case of You.flac",
+                )?),
+                PlaylistEntry {
+                    location: Some("/music/unknown.mp3".to_owned()),
+                    title: Some("Untitled".to_owned()),
+                    ..PlaylistEntry::default()
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("mix.M3U")),
+            Some(PlaylistFormat::M3u8)
+        );
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("mix.jspf")),
+            Some(PlaylistFormat::Jspf)
+        );
+        assert_eq!(PlaylistFormat::from_path(Path::new("mix.txt")), None);
+    }
+
+    #[test]
+    fn test_write_m3u8() -> Result<()> {
+        assert_eq!(
+            playlist()?.write(PlaylistFormat::M3u8)?,
+            "#EXTM3U\n\
+             #PLAYLIST:Joni & friends\n\
+             #EXTINF:215,Joni Mitchell - A Case of You\n\
+             Joni Mitchell/Blue/08 A Case of You.flac\n\
+             #EXTINF:-1,Untitled\n\
+             /music/unknown.mp3\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_m3u8() {
+        let text = "#EXTM3U\r\n#EXTINF:123.4,Nick Drake - Pink Moon\r\n\r\n../Pink Moon.mp3\r\n\
+                    #EXTINF:-1,\r\nC:\\Music\\track.mp3\r\nplain.ogg\r\n";
+
+        let playlist = PlaylistFile::parse(PlaylistFormat::M3u8, text);
+
+        assert_eq!(
+            playlist.ok().map(|playlist| playlist.entries),
+            Some(vec![
+                PlaylistEntry {
+                    location: Some("../Pink Moon.mp3".to_owned()),
+                    title: Some("Pink Moon".to_owned()),
+                    artist: Some("Nick Drake".to_owned()),
+                    duration: Some(123),
+                    ..PlaylistEntry::default()
+                },
+                PlaylistEntry {
+                    location: Some("C:\\Music\\track.mp3".to_owned()),
+                    ..PlaylistEntry::default()
+                },
+                PlaylistEntry {
+                    location: Some("plain.ogg".to_owned()),
+                    ..PlaylistEntry::default()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_xspf_round_trip() -> Result<()> {
+        let text = playlist()?.write(PlaylistFormat::Xspf)?;
+
+        assert!(text.contains("<annotation>For &lt;Sunday&gt; mornings</annotation>"));
+        assert!(text.contains("<identifier>https://musicbrainz.org/recording/mb-s-1</identifier>"));
+        assert!(text.contains("<duration>215000</duration>"));
+        assert!(text
+            .contains("<location>Joni%20Mitchell/Blue/08%20A%20Case%20of%20You.flac</location>"));
+
+        assert_eq!(
+            PlaylistFile::parse(PlaylistFormat::Xspf, &text)?,
+            playlist()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jspf_round_trip() -> Result<()> {
+        let text = playlist()?.write(PlaylistFormat::Jspf)?;
+
+        assert!(text.contains("\"Joni%20Mitchell/Blue/08%20A%20Case%20of%20You.flac\""));
+        assert_eq!(
+            PlaylistFile::parse(PlaylistFormat::Jspf, &text)?,
+            playlist()?
+        );
+
+        let single_location = r#"{"playlist": {"track": [{"location": "a.mp3", "title": "A"}]}}"#;
+        let parsed = PlaylistFile::parse(PlaylistFormat::Jspf, single_location)?;
+
+        assert_eq!(parsed.entries[0].location.as_deref(), Some("a.mp3"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_by_path_suffix() -> Result<()> {
+        let library = vec![song(
+            "s-1",
+            "Blue",
+            "Joni Mitchell",
+            "Joni Mitchell/Blue/06 Blue.flac",
+        )?];
+        let matcher = SongMatcher::new(&library);
+
+        let id = |location| {
+            matcher
+                .by_path(location)
+                .map(|song| song.id.get_ref().as_str())
+        };
+
+        assert_eq!(id("Joni Mitchell/Blue/06 Blue.flac"), Some("s-1"));
+        assert_eq!(
+            id("file:///home/me/Music/Joni Mitchell/Blue/06 Blue.flac"),
+            Some("s-1")
+        );
+        assert_eq!(
+            id("file:///home/me/Music/Joni%20Mitchell/Blue/06%20Blue.flac"),
+            Some("s-1")
+        );
+        assert_eq!(
+            id("D:\\Music\\Joni Mitchell\\Blue\\06 Blue.flac"),
+            Some("s-1")
+        );
+        assert_eq!(id("Other/06 Blue.flac"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_match() -> Result<()> {
+        let candidates = vec![
+            song("s-1", "River", "Ellie Goulding", "a")?,
+            song("s-2", "River (Remastered)", "Joni Mitchell", "b")?,
+        ];
+
+        let entry = PlaylistEntry {
+            title: Some("River".to_owned()),
+            artist: Some("Joni Mitchell".to_owned()),
+            duration: Some(220),
+            ..PlaylistEntry::default()
+        };
+
+        assert_eq!(
+            best_match(&entry, &candidates).map(|song| &song.id),
+            Some(&candidates[1].id)
+        );
+
+        let other_version = PlaylistEntry {
+            duration: Some(400),
+            ..entry
+        };
+
+        assert!(best_match(&other_version, &candidates).is_none());
+
+        Ok(())
+    }
+}
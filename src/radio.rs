@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+use crate::api_types::Song;
+use crate::client::{RandomSongsFilter, SubsonicClient};
+use crate::types::{ArtistId, SongId};
+
+pub const RADIO_BATCH_SIZE: usize = 10;
+pub const RADIO_RECENT_LIMIT: usize = 100;
+
+/// Keeps a play queue topped up with songs similar to what is playing.
+///
+/// Songs are looked up from the artist of the current song, falling back to
+/// the seed artist, the artist's top songs and finally random songs.
+pub struct Radio {
+    artist: Option<ArtistId>,
+    filter: RandomSongsFilter,
+    recent: VecDeque<SongId>,
+}
+
+impl Radio {
+    pub fn new(artist: Option<ArtistId>, filter: RandomSongsFilter) -> Self {
+        Self {
+            artist,
+            filter,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn remember(&mut self, id: &SongId) {
+        if self.recent.len() == RADIO_RECENT_LIMIT {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(id.clone());
+    }
+
+    pub fn pick(&self, candidates: Vec<Song>, queued: &VecDeque<Song>) -> Vec<Song> {
+        let mut picked: Vec<Song> = Vec::new();
+
+        for song in candidates {
+            let seen = self.recent.contains(&song.id)
+                || queued.iter().any(|queued| queued.id == song.id)
+                || picked.iter().any(|picked| picked.id == song.id);
+
+            if !seen {
+                picked.push(song);
+            }
+
+            if picked.len() == RADIO_BATCH_SIZE {
+                break;
+            }
+        }
+
+        picked
+    }
+
+    /// The next songs to queue, none if every source failed, so that playback goes on
+    /// with what is queued and the next top-up tries again.
+    pub async fn next_batch(
+        &self,
+        client: &SubsonicClient,
+        current: Option<&Song>,
+        queued: &VecDeque<Song>,
+    ) -> Vec<Song> {
+        let count = Some(RADIO_BATCH_SIZE as u64 * 2);
+
+        let artist_id = current
+            .and_then(|song| song.artist_id.as_ref())
+            .or(self.artist.as_ref());
+
+        // Servers without similar or top songs, such as those lacking Last.fm, fall
+        // through to the next source.
+        if let Some(artist_id) = artist_id {
+            match client.similar_songs2(artist_id, count).await {
+                Ok(songs) => {
+                    let songs = self.pick(songs, queued);
+
+                    if !songs.is_empty() {
+                        return songs;
+                    }
+                }
+                Err(e) => eprintln!("Could not find similar songs: {e}"),
+            }
+        }
+
+        if let Some(artist) = current.and_then(|song| song.artist.as_deref()) {
+            match client.top_songs(artist, count).await {
+                Ok(songs) => {
+                    let songs = self.pick(songs, queued);
+
+                    if !songs.is_empty() {
+                        return songs;
+                    }
+                }
+                Err(e) => eprintln!("Could not find top songs by {artist}: {e}"),
+            }
+        }
+
+        match client.random_songs(count, &self.filter).await {
+            Ok(songs) => self.pick(songs, queued),
+            Err(e) => {
+                eprintln!("Could not find random songs: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use crate::types::Strong;
+
+    use super::*;
+
+    fn song(id: &str) -> Result<Song> {
+        Ok(serde_json::from_value(
+            json!({ "id": id, "isDir": false, "title": id }),
+        )?)
+    }
+
+    fn ids(songs: &[Song]) -> Vec<&str> {
+        songs
+            .iter()
+            .map(|song| song.id.get_ref().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_skips_recent_and_queued_songs() -> Result<()> {
+        let mut radio = Radio::new(None, RandomSongsFilter::default());
+
+        radio.remember(&SongId::unchecked("a"));
+
+        let queued = VecDeque::from([song("b")?]);
+        let candidates = vec![song("a")?, song("b")?, song("c")?, song("c")?, song("d")?];
+
+        assert_eq!(ids(&radio.pick(candidates, &queued)), vec!["c", "d"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pick_limits_batch_size() -> Result<()> {
+        let radio = Radio::new(None, RandomSongsFilter::default());
+
+        let candidates = (0..RADIO_BATCH_SIZE * 2)
+            .map(|n| song(&n.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            radio.pick(candidates, &VecDeque::new()).len(),
+            RADIO_BATCH_SIZE
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remember_forgets_oldest_songs() -> Result<()> {
+        let mut radio = Radio::new(None, RandomSongsFilter::default());
+
+        for n in 0..=RADIO_RECENT_LIMIT {
+            radio.remember(&SongId::unchecked(n.to_string()));
+        }
+
+        let candidates = vec![song("0")?, song("1")?];
+
+        assert_eq!(ids(&radio.pick(candidates, &VecDeque::new())), vec!["0"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,457 @@
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+use anyhow::Result;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::api_types::Song;
+use crate::error::ValidationError;
+
+/// Lowercases `text` and strips accents, so that `Beyoncé` matches `beyonce`.
+pub fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+
+    for c in text.nfkd().filter(|c| !is_combining_mark(*c)) {
+        // Letters that do not decompose into a base letter and an accent.
+        match c {
+            'ß' => folded.push_str("ss"),
+            'æ' | 'Æ' => folded.push_str("ae"),
+            'œ' | 'Œ' => folded.push_str("oe"),
+            'ø' | 'Ø' => folded.push('o'),
+            'ł' | 'Ł' => folded.push('l'),
+            'đ' | 'Đ' => folded.push('d'),
+            'þ' | 'Þ' => folded.push_str("th"),
+            c => folded.extend(c.to_lowercase()),
+        }
+    }
+
+    folded
+}
+
+fn is_word_start(haystack: &[char], index: usize) -> bool {
+    index == 0 || !haystack[index - 1].is_alphanumeric()
+}
+
+/// Scores how well the folded `needle` matches the folded `haystack`, or `None` if its
+/// characters do not all appear in order.
+///
+/// Exact and substring matches score highest, then matches at the start of words and
+/// runs of consecutive characters.
+pub fn fuzzy_score(needle: &str, haystack: &str) -> Option<u32> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+
+    let length = needle.chars().count() as u32;
+
+    if haystack == needle {
+        return Some(100 + length * 10);
+    }
+
+    let haystack_chars: Vec<char> = haystack.chars().collect();
+
+    if let Some(byte_index) = haystack.find(needle) {
+        let index = haystack[..byte_index].chars().count();
+        let word_start = if is_word_start(&haystack_chars, index) {
+            20
+        } else {
+            0
+        };
+        let field_start = if index == 0 { 10 } else { 0 };
+
+        return Some(50 + word_start + field_start + length * 8);
+    }
+
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut start = 0;
+
+    for c in needle.chars() {
+        let index = start + haystack_chars[start..].iter().position(|h| *h == c)?;
+
+        score += 1;
+
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 3;
+        }
+
+        if is_word_start(&haystack_chars, index) {
+            score += 4;
+        }
+
+        previous = Some(index);
+        start = index + 1;
+    }
+
+    Some(score)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextField {
+    Artist,
+    Album,
+    Title,
+    Genre,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub(crate) fn matches(self, value: u64, bound: u64) -> bool {
+        match self {
+            Comparison::Less => value < bound,
+            Comparison::LessOrEqual => value <= bound,
+            Comparison::Equal => value == bound,
+            Comparison::GreaterOrEqual => value >= bound,
+            Comparison::Greater => value > bound,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Folded text that the field must contain, unlike the fuzzily matched terms.
+    Text(TextField, String),
+    Year(Comparison, u64),
+}
+
+/// A search such as `blue artist:"miles davis" year:>1955`, where plain words may match
+/// the title, artist or album and `field:value` filters narrow the results.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Folded words, all of which must match.
+    pub terms: Vec<String>,
+    pub filters: Vec<Filter>,
+}
+
+// Splits on whitespace, keeping text in double quotes together.
+fn tokens(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn parse_year(value: &str) -> Result<Filter, ValidationError> {
+    let (comparison, year) = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ]
+    .into_iter()
+    .find_map(|(prefix, comparison)| Some((comparison, value.strip_prefix(prefix)?)))
+    .unwrap_or((Comparison::Equal, value));
+
+    let year = year.parse().map_err(|_| {
+        ValidationError(format!(
+            "Invalid filter year:{value}, expected a year such as year:1999 or year:>2010."
+        ))
+    })?;
+
+    Ok(Filter::Year(comparison, year))
+}
+
+impl FromStr for Query {
+    type Err = ValidationError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let mut parsed = Query::default();
+
+        for token in tokens(query) {
+            let field = token
+                .split_once(':')
+                .map(|(field, value)| (field.to_lowercase(), value));
+
+            let filter = match field {
+                Some((field, value)) if field == "year" => Some(parse_year(value)?),
+                Some((field, value)) => {
+                    let field = match field.as_str() {
+                        "artist" => Some(TextField::Artist),
+                        "album" => Some(TextField::Album),
+                        "title" => Some(TextField::Title),
+                        "genre" => Some(TextField::Genre),
+                        // Titles such as `Re:Stacks` are plain words.
+                        _ => None,
+                    };
+
+                    field.map(|field| Filter::Text(field, fold(value)))
+                }
+                None => None,
+            };
+
+            match filter {
+                Some(filter) => parsed.filters.push(filter),
+                None => parsed.terms.push(fold(&token)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+struct Entry {
+    song: Song,
+    title: String,
+    artist: String,
+    album: String,
+    genre: String,
+}
+
+impl Entry {
+    fn new(song: Song) -> Self {
+        let fold_field = |value: &Option<String>| value.as_deref().map(fold).unwrap_or_default();
+
+        Self {
+            title: fold(&song.title),
+            artist: fold_field(&song.artist),
+            album: fold_field(&song.album),
+            genre: fold_field(&song.genre),
+            song,
+        }
+    }
+
+    fn text(&self, field: TextField) -> &str {
+        match field {
+            TextField::Artist => &self.artist,
+            TextField::Album => &self.album,
+            TextField::Title => &self.title,
+            TextField::Genre => &self.genre,
+        }
+    }
+
+    fn passes(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::Text(field, value) => self.text(*field).contains(value.as_str()),
+            Filter::Year(comparison, year) => self
+                .song
+                .year
+                .is_some_and(|value| comparison.matches(value, *year)),
+        }
+    }
+
+    fn score(&self, query: &Query) -> Option<u32> {
+        if !query.filters.iter().all(|filter| self.passes(filter)) {
+            return None;
+        }
+
+        query.terms.iter().try_fold(0, |total, term| {
+            let best = [&self.title, &self.artist, &self.album]
+                .into_iter()
+                .filter_map(|field| fuzzy_score(term, field))
+                .max()?;
+
+            Some(total + best)
+        })
+    }
+}
+
+/// An in-memory index of songs for fast, fuzzy find-as-you-type searches.
+pub struct SearchIndex {
+    entries: Vec<Entry>,
+}
+
+impl SearchIndex {
+    pub fn new(songs: impl IntoIterator<Item = Song>) -> Self {
+        let entries = songs
+            .into_iter()
+            .filter(|song| !song.is_dir)
+            .map(Entry::new)
+            .collect();
+
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The best matches first, or songs in artist, album and track order for queries
+    /// with only filters.
+    pub fn search(&self, query: &Query, limit: usize) -> Vec<&Song> {
+        let mut matches: Vec<(u32, &Entry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| Some((entry.score(query)?, entry)))
+            .collect();
+
+        matches.sort_by_key(|(score, entry)| {
+            (
+                Reverse(*score),
+                &entry.artist,
+                &entry.album,
+                entry.song.disc_number,
+                entry.song.track,
+            )
+        });
+
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, entry)| &entry.song)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn index() -> Result<SearchIndex> {
+        let songs = [
+            ("Halo", "Beyoncé", "I Am... Sasha Fierce", "R&B", 2008),
+            ("Formation", "Beyoncé", "Lemonade", "R&B", 2016),
+            ("So What", "Miles Davis", "Kind of Blue", "Jazz", 1959),
+            ("Blue in Green", "Miles Davis", "Kind of Blue", "Jazz", 1959),
+            ("Jóga", "Björk", "Homogenic", "Electronic", 1997),
+        ];
+
+        let songs = songs
+            .into_iter()
+            .enumerate()
+            .map(|(n, (title, artist, album, genre, year))| {
+                serde_json::from_value(json!({
+                    "id": format!("s-{n}"),
+                    "isDir": false,
+                    "title": title,
+                    "artist": artist,
+                    "album": album,
+                    "genre": genre,
+                    "year": year
+                }))
+            })
+            .collect::<serde_json::Result<Vec<Song>>>()?;
+
+        Ok(SearchIndex::new(songs))
+    }
+
+    fn titles(index: &SearchIndex, query: &str) -> Result<Vec<String>> {
+        Ok(index
+            .search(&query.parse()?, 10)
+            .into_iter()
+            .map(|song| song.title.clone())
+            .collect())
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Beyoncé"), "beyonce");
+        assert_eq!(fold("Björk Guðmundsdóttir"), "bjork guðmundsdottir");
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("Røyksopp"), "royksopp");
+    }
+
+    #[test]
+    fn test_fuzzy_score_prefers_closer_matches() {
+        let exact = fuzzy_score("blue", "blue");
+        let word = fuzzy_score("blue", "kind of blue");
+        let inside = fuzzy_score("lue", "kind of blue");
+        let scattered = fuzzy_score("kob", "kind of blue");
+
+        assert!(exact > word);
+        assert!(word > inside);
+        assert!(inside > scattered);
+        assert!(scattered.is_some());
+        assert_eq!(fuzzy_score("bleu", "kind of blue"), None);
+    }
+
+    #[test]
+    fn test_search_folds_diacritics() -> Result<()> {
+        let index = index()?;
+
+        assert_eq!(titles(&index, "bjork")?, vec!["Jóga"]);
+        assert_eq!(titles(&index, "JOGA")?, vec!["Jóga"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_ranks_best_matches_first() -> Result<()> {
+        let index = index()?;
+
+        assert_eq!(titles(&index, "blue")?, vec!["Blue in Green", "So What"]);
+        assert_eq!(titles(&index, "mls what")?, vec!["So What"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_filters() -> Result<()> {
+        let index = index()?;
+
+        assert_eq!(
+            titles(&index, "artist:beyonce year:>2010")?,
+            vec!["Formation"]
+        );
+        assert_eq!(
+            titles(&index, "year:<=1997 genre:electronic")?,
+            vec!["Jóga"]
+        );
+        assert_eq!(
+            titles(&index, "album:\"kind of blue\"")?,
+            vec!["So What", "Blue in Green"]
+        );
+        assert_eq!(titles(&index, "halo year:2016")?, Vec::<String>::new());
+        assert_eq!(titles(&index, "artist:mls")?, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_query() -> Result<()> {
+        let query: Query = "Re:Stacks artist:\"Bon Iver\" year:>=2007".parse()?;
+
+        assert_eq!(
+            query,
+            Query {
+                terms: vec!["re:stacks".to_owned()],
+                filters: vec![
+                    Filter::Text(TextField::Artist, "bon iver".to_owned()),
+                    Filter::Year(Comparison::GreaterOrEqual, 2007),
+                ],
+            }
+        );
+
+        match "year:recent".parse::<Query>() {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Invalid filter year:recent, expected a year such as year:1999 or year:>2010."
+            ),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,592 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Deserializer};
+
+use crate::api_types::{AlbumID3, AlbumID3WithSongs, Song};
+use crate::client::SubsonicClient;
+use crate::error::ValidationError;
+use crate::search::{fold, Comparison};
+use crate::types::{PlaylistId, SongId, Strong};
+
+const DEFAULT_LIMIT: u64 = 100;
+
+const UNITS: [(&str, u64); 5] = [
+    ("w", 7 * 24 * 60 * 60),
+    ("d", 24 * 60 * 60),
+    ("h", 60 * 60),
+    ("m", 60),
+    ("s", 1),
+];
+
+/// Parses a number of seconds with an optional unit, such as `90`, `5m`, `12h`, `30d` or `2w`.
+pub fn parse_seconds(text: &str) -> Option<u64> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+
+    let (_, multiplier) = UNITS.iter().find(|(name, _)| *name == unit.trim())?;
+
+    number.parse::<u64>().ok()?.checked_mul(*multiplier)
+}
+
+fn format_seconds(seconds: u64) -> String {
+    UNITS
+        .iter()
+        .find(|(_, multiplier)| seconds > 0 && seconds.is_multiple_of(*multiplier))
+        .map(|(unit, multiplier)| format!("{}{unit}", seconds / multiplier))
+        .unwrap_or_else(|| format!("{seconds}s"))
+}
+
+fn symbol(comparison: Comparison) -> &'static str {
+    match comparison {
+        Comparison::Less => "<",
+        Comparison::LessOrEqual => "<=",
+        Comparison::Equal => "=",
+        Comparison::GreaterOrEqual => ">=",
+        Comparison::Greater => ">",
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberField {
+    Year,
+    Rating,
+    PlayCount,
+    Duration,
+    Bpm,
+}
+
+impl NumberField {
+    const ALL: [(&'static str, NumberField); 5] = [
+        ("year", NumberField::Year),
+        ("rating", NumberField::Rating),
+        ("play_count", NumberField::PlayCount),
+        ("duration", NumberField::Duration),
+        ("bpm", NumberField::Bpm),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    fn value(self, song: &Song, album: &AlbumID3) -> Option<u64> {
+        match self {
+            NumberField::Year => song.year.or(album.year),
+            // Servers leave out ratings and play counts that are zero.
+            NumberField::Rating => Some(song.user_rating.unwrap_or_default()),
+            NumberField::PlayCount => Some(song.play_count.unwrap_or_default()),
+            NumberField::Duration => song.duration,
+            NumberField::Bpm => song.bpm.filter(|bpm| *bpm > 0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenreComparison {
+    Is,
+    IsNot,
+    Contains,
+}
+
+/// One condition of a smart playlist, such as `genre = jazz`, `year >= 1970`,
+/// `last_played > 30d` or `not starred`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// Whether the song or its album is starred.
+    Starred(bool),
+    Genre(GenreComparison, String),
+    Number(NumberField, Comparison, u64),
+    /// Seconds since the song was last played, where songs never played count as played
+    /// longer ago than anything.
+    LastPlayed(Comparison, u64),
+}
+
+const FIELD_NAMES: &str = "starred, genre, year, rating, play_count, last_played, duration, bpm";
+
+fn comparison(rule: &str, op: &str) -> Result<Comparison, ValidationError> {
+    match op {
+        "<" => Ok(Comparison::Less),
+        "<=" => Ok(Comparison::LessOrEqual),
+        "=" => Ok(Comparison::Equal),
+        ">=" => Ok(Comparison::GreaterOrEqual),
+        ">" => Ok(Comparison::Greater),
+        _ => Err(ValidationError(format!(
+            "Invalid comparison {op} in smart playlist rule `{rule}`, expected one of <, <=, =, >=, >."
+        ))),
+    }
+}
+
+fn seconds(rule: &str, value: &str) -> Result<u64, ValidationError> {
+    parse_seconds(value).ok_or_else(|| {
+        ValidationError(format!(
+            "Invalid duration {value} in smart playlist rule `{rule}`, expected a number with an optional unit such as 90s, 5m, 12h, 30d or 2w."
+        ))
+    })
+}
+
+impl FromStr for Rule {
+    type Err = ValidationError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+
+        match rule {
+            "starred" => return Ok(Rule::Starred(true)),
+            "not starred" => return Ok(Rule::Starred(false)),
+            _ => {}
+        }
+
+        let invalid = || {
+            ValidationError(format!(
+                "Invalid smart playlist rule `{rule}`, expected a field, a comparison and a value such as `year >= 1970`."
+            ))
+        };
+
+        let start = rule.find(['<', '>', '=', '!', '~']).ok_or_else(invalid)?;
+        let rest = &rule[start..];
+        let (op, value) = rest.split_at(if rest[1..].starts_with('=') { 2 } else { 1 });
+
+        let field = rule[..start].trim();
+        let value = value.trim();
+
+        if field.is_empty() || value.is_empty() {
+            return Err(invalid());
+        }
+
+        match field {
+            "genre" => {
+                let comparison = match op {
+                    "=" => GenreComparison::Is,
+                    "!=" => GenreComparison::IsNot,
+                    "~" => GenreComparison::Contains,
+                    _ => {
+                        return Err(ValidationError(format!(
+                            "Invalid comparison {op} in smart playlist rule `{rule}`, expected one of =, !=, ~."
+                        )))
+                    }
+                };
+
+                Ok(Rule::Genre(comparison, value.to_owned()))
+            }
+            "last_played" => Ok(Rule::LastPlayed(
+                comparison(rule, op)?,
+                seconds(rule, value)?,
+            )),
+            _ => {
+                let field = NumberField::ALL
+                    .iter()
+                    .find(|(name, _)| *name == field)
+                    .map(|(_, field)| *field)
+                    .ok_or_else(|| {
+                        ValidationError(format!(
+                            "Unknown field {field} in smart playlist rule `{rule}`, expected one of {FIELD_NAMES}."
+                        ))
+                    })?;
+
+                let bound = match field {
+                    NumberField::Duration => seconds(rule, value)?,
+                    _ => value.parse().map_err(|_| {
+                        ValidationError(format!(
+                            "Invalid number {value} in smart playlist rule `{rule}`."
+                        ))
+                    })?,
+                };
+
+                Ok(Rule::Number(field, comparison(rule, op)?, bound))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Starred(true) => write!(fmt, "starred"),
+            Rule::Starred(false) => write!(fmt, "not starred"),
+            Rule::Genre(comparison, genre) => {
+                let op = match comparison {
+                    GenreComparison::Is => "=",
+                    GenreComparison::IsNot => "!=",
+                    GenreComparison::Contains => "~",
+                };
+
+                write!(fmt, "genre {op} {genre}")
+            }
+            Rule::Number(NumberField::Duration, comparison, bound) => {
+                write!(
+                    fmt,
+                    "duration {} {}",
+                    symbol(*comparison),
+                    format_seconds(*bound)
+                )
+            }
+            Rule::Number(field, comparison, bound) => {
+                write!(fmt, "{} {} {bound}", field.name(), symbol(*comparison))
+            }
+            Rule::LastPlayed(comparison, bound) => {
+                write!(
+                    fmt,
+                    "last_played {} {}",
+                    symbol(*comparison),
+                    format_seconds(*bound)
+                )
+            }
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, song: &Song, album: &AlbumID3, now: DateTime<Utc>) -> bool {
+        match self {
+            Rule::Starred(starred) => {
+                (song.starred.is_some() || album.starred.is_some()) == *starred
+            }
+            Rule::Genre(comparison, genre) => {
+                let genre = fold(genre);
+                let mut genres = song_genres(song, album).map(fold);
+
+                match comparison {
+                    GenreComparison::Is => genres.any(|name| name == genre),
+                    GenreComparison::IsNot => !genres.any(|name| name == genre),
+                    GenreComparison::Contains => genres.any(|name| name.contains(&genre)),
+                }
+            }
+            Rule::Number(field, comparison, bound) => field
+                .value(song, album)
+                .is_some_and(|value| comparison.matches(value, *bound)),
+            Rule::LastPlayed(comparison, bound) => match song.played {
+                Some(played) => {
+                    let ago = u64::try_from((now - played).num_seconds()).unwrap_or_default();
+
+                    comparison.matches(ago, *bound)
+                }
+                None => matches!(comparison, Comparison::Greater | Comparison::GreaterOrEqual),
+            },
+        }
+    }
+}
+
+// Prefers the list of genres servers with several genres per song send.
+fn song_genres<'a>(song: &'a Song, album: &'a AlbumID3) -> impl Iterator<Item = &'a str> {
+    let genres: Vec<&str> = match &song.genres {
+        Some(genres) if !genres.is_empty() => {
+            genres.iter().map(|genre| genre.name.as_str()).collect()
+        }
+        _ => song
+            .genre
+            .as_deref()
+            .or(album.genre.as_deref())
+            .into_iter()
+            .collect(),
+    };
+
+    genres.into_iter()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Random,
+    /// Recently added albums first.
+    Newest,
+    MostPlayed,
+    /// Songs never played first.
+    LeastRecentlyPlayed,
+    Year,
+}
+
+/// A playlist of the songs in the library that meet all of its rules, as defined in a
+/// `[smart_playlists.<name>]` section.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct SmartPlaylist {
+    pub rules: Vec<Rule>,
+    pub order: Order,
+    /// Maximum number of songs, 0 for no limit.
+    pub limit: u64,
+    /// Name of the playlist on the server, instead of the section name.
+    pub name: Option<String>,
+}
+
+impl Default for SmartPlaylist {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            order: Order::default(),
+            limit: DEFAULT_LIMIT,
+            name: None,
+        }
+    }
+}
+
+impl SmartPlaylist {
+    pub fn description(&self) -> String {
+        if self.rules.is_empty() {
+            return "all songs".to_owned();
+        }
+
+        self.rules
+            .iter()
+            .map(Rule::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn songs(
+        &self,
+        albums: &[AlbumID3WithSongs],
+        now: DateTime<Utc>,
+        rng: &mut impl Rng,
+    ) -> Vec<Song> {
+        let mut seen = HashSet::new();
+
+        let mut songs: Vec<(&AlbumID3, &Song)> = albums
+            .iter()
+            .flat_map(|album| album.song.iter().map(|song| (&album.album_data, song)))
+            .filter(|(album, song)| {
+                !song.is_dir && self.rules.iter().all(|rule| rule.matches(song, album, now))
+            })
+            .filter(|(_, song)| seen.insert(&song.id))
+            .collect();
+
+        match self.order {
+            Order::Random => songs.shuffle(rng),
+            Order::Newest => songs.sort_by_key(|(album, _)| Reverse(album.created)),
+            Order::MostPlayed => {
+                songs.sort_by_key(|(_, song)| Reverse(song.play_count.unwrap_or_default()))
+            }
+            Order::LeastRecentlyPlayed => songs.sort_by_key(|(_, song)| song.played),
+            Order::Year => songs.sort_by_key(|(album, song)| song.year.or(album.year)),
+        }
+
+        let limit = match self.limit {
+            0 => usize::MAX,
+            limit => usize::try_from(limit).unwrap_or(usize::MAX),
+        };
+
+        songs
+            .into_iter()
+            .take(limit)
+            .map(|(_, song)| song.clone())
+            .collect()
+    }
+}
+
+/// Replaces the songs of the user's playlist called `name`, creating it if there is none,
+/// and describes the rules in its comment.
+pub async fn save_to_server(
+    client: &SubsonicClient,
+    name: &str,
+    playlist: &SmartPlaylist,
+    songs: &[SongId],
+) -> Result<PlaylistId> {
+    let existing = client.playlists().await?.into_iter().find(|existing| {
+        existing.name == name
+            && existing
+                .owner
+                .as_ref()
+                .is_none_or(|owner| owner == client.username.get_ref())
+    });
+
+    let id = match existing {
+        Some(existing) => {
+            client.replace_playlist(&existing.id, songs).await?;
+            existing.id
+        }
+        None => client.create_playlist(name, songs).await?.playlist_data.id,
+    };
+
+    let comment = format!("Smart playlist: {}", playlist.description());
+    client.update_playlist(&id, None, Some(&comment)).await?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    use super::*;
+
+    fn album(songs: serde_json::Value) -> Result<AlbumID3WithSongs> {
+        Ok(serde_json::from_value(json!({
+            "id": "al-1",
+            "created": "2024-01-01T00:00:00Z",
+            "duration": 1000,
+            "name": "Kind of Blue",
+            "songCount": 3,
+            "genre": "Jazz",
+            "year": 1959,
+            "song": songs
+        }))?)
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2024-06-01T00:00:00Z".parse().unwrap_or_default()
+    }
+
+    fn titles(songs: &[Song]) -> Vec<&str> {
+        songs.iter().map(|song| song.title.as_str()).collect()
+    }
+
+    fn smart(rules: &[&str], order: Order) -> Result<SmartPlaylist> {
+        Ok(SmartPlaylist {
+            rules: rules
+                .iter()
+                .map(|rule| rule.parse())
+                .collect::<Result<_, _>>()?,
+            order,
+            ..SmartPlaylist::default()
+        })
+    }
+
+    #[test]
+    fn test_parse_rules() -> Result<()> {
+        assert_eq!("starred".parse::<Rule>()?, Rule::Starred(true));
+        assert_eq!(
+            "genre~jazz".parse::<Rule>()?,
+            Rule::Genre(GenreComparison::Contains, "jazz".to_owned())
+        );
+        assert_eq!(
+            " play_count <= 3 ".parse::<Rule>()?,
+            Rule::Number(NumberField::PlayCount, Comparison::LessOrEqual, 3)
+        );
+        assert_eq!(
+            "duration > 5m".parse::<Rule>()?,
+            Rule::Number(NumberField::Duration, Comparison::Greater, 300)
+        );
+        assert_eq!(
+            "last_played > 30d".parse::<Rule>()?,
+            Rule::LastPlayed(Comparison::Greater, 30 * 24 * 60 * 60)
+        );
+
+        for rule in [
+            "not starred",
+            "genre != Hip-Hop",
+            "year >= 1970",
+            "duration < 90s",
+            "last_played <= 2w",
+        ] {
+            assert_eq!(rule.parse::<Rule>()?.to_string(), rule);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for (rule, message) in [
+            (
+                "starred jazz",
+                "Invalid smart playlist rule `starred jazz`, expected a field, a comparison and a value such as `year >= 1970`.",
+            ),
+            (
+                "mood = happy",
+                "Unknown field mood in smart playlist rule `mood = happy`, expected one of starred, genre, year, rating, play_count, last_played, duration, bpm.",
+            ),
+            (
+                "genre > jazz",
+                "Invalid comparison > in smart playlist rule `genre > jazz`, expected one of =, !=, ~.",
+            ),
+            (
+                "year ~ 1970",
+                "Invalid comparison ~ in smart playlist rule `year ~ 1970`, expected one of <, <=, =, >=, >.",
+            ),
+            (
+                "year >= seventies",
+                "Invalid number seventies in smart playlist rule `year >= seventies`.",
+            ),
+            (
+                "last_played > 1 month",
+                "Invalid duration 1 month in smart playlist rule `last_played > 1 month`, expected a number with an optional unit such as 90s, 5m, 12h, 30d or 2w.",
+            ),
+        ] {
+            match rule.parse::<Rule>() {
+                Ok(_) => panic!("expected an error"),
+                Err(e) => assert_eq!(e.to_string(), message),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules_fall_back_to_the_album() -> Result<()> {
+        let mut album = album(json!([
+            {"id": "s-1", "isDir": false, "title": "So What"},
+            {"id": "s-2", "isDir": false, "title": "Blue in Green", "genre": "Modal", "year": 1960}
+        ]))?;
+
+        let playlist = smart(&["genre = jazz", "year < 1960", "starred"], Order::Year)?;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+        assert!(playlist.songs(&[album], now(), &mut rng).is_empty());
+
+        album = self::album(json!([
+            {"id": "s-1", "isDir": false, "title": "So What", "starred": "2024-01-01T00:00:00Z"},
+            {"id": "s-2", "isDir": false, "title": "Blue in Green", "genre": "Modal", "year": 1960}
+        ]))?;
+
+        assert_eq!(
+            titles(&playlist.songs(&[album], now(), &mut rng)),
+            vec!["So What"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_played_and_order() -> Result<()> {
+        let played = |days| (now() - TimeDelta::days(days)).to_rfc3339();
+
+        let albums = [album(json!([
+            {"id": "s-1", "isDir": false, "title": "So What", "played": played(3), "playCount": 9},
+            {"id": "s-2", "isDir": false, "title": "Freddie Freeloader", "played": played(45), "playCount": 4},
+            {"id": "s-3", "isDir": false, "title": "Blue in Green"},
+            {"id": "s-3", "isDir": false, "title": "Blue in Green"}
+        ]))?];
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+        let playlist = smart(&["last_played > 30d"], Order::LeastRecentlyPlayed)?;
+        assert_eq!(
+            titles(&playlist.songs(&albums, now(), &mut rng)),
+            vec!["Blue in Green", "Freddie Freeloader"]
+        );
+
+        let playlist = smart(&["last_played < 30d"], Order::Random)?;
+        assert_eq!(
+            titles(&playlist.songs(&albums, now(), &mut rng)),
+            vec!["So What"]
+        );
+
+        let mut playlist = smart(&[], Order::MostPlayed)?;
+        playlist.limit = 2;
+        assert_eq!(
+            titles(&playlist.songs(&albums, now(), &mut rng)),
+            vec!["So What", "Freddie Freeloader"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,662 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::api_types::{AlbumID3, AlbumID3WithSongs, Song};
+use crate::client::{AlbumListType, SubsonicClient, MAX_ALBUM_LIST_SIZE};
+use crate::config::SubsonicConfig;
+use crate::types::{AlbumId, ArtistId, SongId, Strong};
+
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE artists (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE albums (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        artist TEXT,
+        artist_id TEXT,
+        year INTEGER,
+        genre TEXT,
+        cover_art TEXT,
+        song_count INTEGER NOT NULL,
+        duration INTEGER NOT NULL,
+        created INTEGER NOT NULL
+    );
+    CREATE TABLE songs (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        album TEXT,
+        album_id TEXT,
+        artist TEXT,
+        artist_id TEXT,
+        disc_number INTEGER,
+        track INTEGER,
+        year INTEGER,
+        genre TEXT,
+        duration INTEGER,
+        size INTEGER,
+        suffix TEXT,
+        content_type TEXT,
+        path TEXT,
+        music_brainz_id TEXT
+    );
+    CREATE INDEX songs_album_id ON songs (album_id);
+    CREATE TABLE local_files (
+        song_id TEXT PRIMARY KEY,
+        path TEXT NOT NULL
+    );
+    CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+";
+
+const SONG_COLUMNS: &str = "id, title, album, album_id, artist, artist_id, disc_number, track, \
+     year, genre, duration, size, suffix, content_type, path, music_brainz_id";
+const ALBUM_COLUMNS: &str =
+    "id, name, artist, artist_id, year, genre, cover_art, song_count, duration, created";
+
+const INDEXES_MODIFIED: &str = "indexes_modified";
+
+/// Where the snapshot of a server's library is kept, one file per server and user.
+pub fn default_snapshot_path(server: &SubsonicConfig) -> Result<PathBuf> {
+    let cache = dirs::cache_dir().context("Could not find a cache directory for this platform.")?;
+    let key = md5::compute(format!("{}@{}", server.username, server.url));
+
+    Ok(cache
+        .join("knuckles")
+        .join(format!("library-{key:x}.sqlite3")))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SnapshotArtist {
+    pub id: ArtistId,
+    pub name: String,
+    pub album_count: u64,
+}
+
+/// A local copy of the artists, albums and songs on a server, for browsing and searching
+/// while it is unreachable, along with where songs have been saved locally.
+pub struct Snapshot {
+    connection: Connection,
+}
+
+fn millis_to_time(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+fn song_from_row(row: &Row) -> rusqlite::Result<Song> {
+    Ok(Song {
+        id: SongId(row.get(0)?),
+        title: row.get(1)?,
+        album: row.get(2)?,
+        album_id: row.get::<_, Option<String>>(3)?.map(AlbumId),
+        artist: row.get(4)?,
+        artist_id: row.get::<_, Option<String>>(5)?.map(ArtistId),
+        disc_number: row.get(6)?,
+        track: row.get(7)?,
+        year: row.get(8)?,
+        genre: row.get(9)?,
+        duration: row.get(10)?,
+        size: row.get(11)?,
+        suffix: row.get(12)?,
+        content_type: row.get(13)?,
+        path: row.get(14)?,
+        music_brainz_id: row.get(15)?,
+        ..Song::default()
+    })
+}
+
+fn album_from_row(row: &Row) -> rusqlite::Result<AlbumID3> {
+    Ok(AlbumID3 {
+        id: AlbumId(row.get(0)?),
+        name: row.get(1)?,
+        artist: row.get(2)?,
+        artist_id: row.get::<_, Option<String>>(3)?.map(ArtistId),
+        year: row.get(4)?,
+        genre: row.get(5)?,
+        cover_art: row.get(6)?,
+        song_count: row.get(7)?,
+        duration: row.get(8)?,
+        created: millis_to_time(row.get(9)?),
+        play_count: None,
+        starred: None,
+    })
+}
+
+fn artist_from_row(row: &Row) -> rusqlite::Result<SnapshotArtist> {
+    Ok(SnapshotArtist {
+        id: ArtistId(row.get(0)?),
+        name: row.get(1)?,
+        album_count: row.get(2)?,
+    })
+}
+
+impl Snapshot {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)
+            .with_context(|| format!("Could not open library snapshot {}.", path.display()))?;
+
+        Self::from_connection(connection)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version != SCHEMA_VERSION {
+            // Snapshots only hold copies, so an old one is simply rebuilt.
+            connection.execute_batch(
+                "DROP TABLE IF EXISTS artists;
+                 DROP TABLE IF EXISTS albums;
+                 DROP TABLE IF EXISTS songs;
+                 DROP TABLE IF EXISTS local_files;
+                 DROP TABLE IF EXISTS meta;",
+            )?;
+            connection.execute_batch(SCHEMA)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+
+        Ok(Self { connection })
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<i64>> {
+        let value = self
+            .connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+
+        Ok(value)
+    }
+
+    /// When the server's indexes last changed, as of the last refresh.
+    pub fn indexes_modified(&self) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.meta(INDEXES_MODIFIED)?.map(millis_to_time))
+    }
+
+    fn albums_by_id(&self) -> Result<HashMap<AlbumId, AlbumID3>> {
+        Ok(self
+            .albums("", [])?
+            .into_iter()
+            .map(|album| (album.id.clone(), album))
+            .collect())
+    }
+
+    /// Stores albums and their songs, replacing the whole library if `replace` is set.
+    pub fn store(
+        &mut self,
+        albums: &[AlbumID3WithSongs],
+        replace: bool,
+        indexes_modified: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+
+        if replace {
+            transaction
+                .execute_batch("DELETE FROM artists; DELETE FROM albums; DELETE FROM songs;")?;
+        }
+
+        for album in albums {
+            let data = &album.album_data;
+
+            if let (Some(id), Some(name)) = (&data.artist_id, &data.artist) {
+                transaction.execute(
+                    "INSERT OR REPLACE INTO artists (id, name) VALUES (?1, ?2)",
+                    params![id.get_ref(), name],
+                )?;
+            }
+
+            transaction.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO albums ({ALBUM_COLUMNS}) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+                ),
+                params![
+                    data.id.get_ref(),
+                    data.name,
+                    data.artist,
+                    data.artist_id.as_ref().map(Strong::get_ref),
+                    data.year,
+                    data.genre,
+                    data.cover_art,
+                    data.song_count,
+                    data.duration,
+                    data.created.timestamp_millis(),
+                ],
+            )?;
+
+            // Songs removed from an album since the last refresh go with it.
+            transaction.execute("DELETE FROM songs WHERE album_id = ?1", [data.id.get_ref()])?;
+
+            for song in &album.song {
+                insert_song(&transaction, song)?;
+            }
+        }
+
+        if let Some(modified) = indexes_modified {
+            transaction.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                params![INDEXES_MODIFIED, modified.timestamp_millis()],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Stores songs on their own, such as downloads from albums not yet in the snapshot.
+    pub fn store_songs(&mut self, songs: &[Song]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+
+        for song in songs.iter().filter(|song| !song.is_dir) {
+            insert_song(&transaction, song)?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    pub fn set_local_file(&self, id: &SongId, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .context("Local file paths must be valid UTF-8.")?;
+
+        self.connection.execute(
+            "INSERT OR REPLACE INTO local_files (song_id, path) VALUES (?1, ?2)",
+            params![id.get_ref(), path],
+        )?;
+
+        Ok(())
+    }
+
+    /// Forgets the song saved at `path`, such as a file deleted by a sync.
+    pub fn remove_local_file(&self, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .context("Local file paths must be valid UTF-8.")?;
+
+        self.connection
+            .execute("DELETE FROM local_files WHERE path = ?1", [path])?;
+
+        Ok(())
+    }
+
+    pub fn local_files(&self) -> Result<HashMap<SongId, PathBuf>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT song_id, path FROM local_files")?;
+
+        let files = statement
+            .query_map([], |row| {
+                Ok((SongId(row.get(0)?), PathBuf::from(row.get::<_, String>(1)?)))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(files)
+    }
+
+    pub fn all_songs(&self) -> Result<Vec<Song>> {
+        self.songs("ORDER BY artist, album, disc_number, track", [])
+    }
+
+    /// Songs saved locally, by artist, album and track.
+    pub fn local_songs(&self) -> Result<Vec<Song>> {
+        self.songs(
+            "JOIN local_files ON local_files.song_id = songs.id \
+             ORDER BY artist, album, disc_number, track",
+            [],
+        )
+    }
+
+    fn songs(&self, clauses: &str, params: impl rusqlite::Params) -> Result<Vec<Song>> {
+        let columns: Vec<_> = SONG_COLUMNS
+            .split(", ")
+            .map(|column| format!("songs.{column}"))
+            .collect();
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM songs {clauses}",
+            columns.join(", ")
+        ))?;
+
+        let songs = statement
+            .query_map(params, song_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(songs)
+    }
+
+    fn albums(&self, clauses: &str, params: impl rusqlite::Params) -> Result<Vec<AlbumID3>> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT {ALBUM_COLUMNS} FROM albums {clauses}"))?;
+
+        let albums = statement
+            .query_map(params, album_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(albums)
+    }
+
+    fn artists(&self, clauses: &str, params: impl rusqlite::Params) -> Result<Vec<SnapshotArtist>> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT artists.id, artists.name, COUNT(albums.id) FROM artists \
+             LEFT JOIN albums ON albums.artist_id = artists.id {clauses}"
+        ))?;
+
+        let artists = statement
+            .query_map(params, artist_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(artists)
+    }
+
+    pub fn all_artists(&self) -> Result<Vec<SnapshotArtist>> {
+        self.artists("GROUP BY artists.id ORDER BY artists.name", [])
+    }
+
+    pub fn artist_albums(&self, id: &ArtistId) -> Result<Vec<AlbumID3>> {
+        self.albums("WHERE artist_id = ?1 ORDER BY year, name", [id.get_ref()])
+    }
+
+    pub fn album_songs(&self, id: &AlbumId) -> Result<Vec<Song>> {
+        self.songs(
+            "WHERE album_id = ?1 ORDER BY disc_number, track",
+            [id.get_ref()],
+        )
+    }
+}
+
+fn insert_song(connection: &Connection, song: &Song) -> Result<()> {
+    connection.execute(
+        &format!(
+            "INSERT OR REPLACE INTO songs ({SONG_COLUMNS}) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)"
+        ),
+        params![
+            song.id.get_ref(),
+            song.title,
+            song.album,
+            song.album_id.as_ref().map(Strong::get_ref),
+            song.artist,
+            song.artist_id.as_ref().map(Strong::get_ref),
+            song.disc_number,
+            song.track,
+            song.year,
+            song.genre,
+            song.duration,
+            song.size,
+            song.suffix,
+            song.content_type,
+            song.path,
+            song.music_brainz_id,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Lists every album on the server, newest first, without their songs.
+async fn list_albums(client: &SubsonicClient) -> Result<Vec<AlbumID3>> {
+    let mut albums = Vec::new();
+
+    loop {
+        let page = client
+            .albums2(
+                AlbumListType::Newest,
+                Some(MAX_ALBUM_LIST_SIZE),
+                Some(albums.len() as u64),
+                None,
+            )
+            .await?;
+
+        let count = page.len() as u64;
+        albums.extend(page);
+
+        if count < MAX_ALBUM_LIST_SIZE {
+            return Ok(albums);
+        }
+    }
+}
+
+/// Fetches every album with its songs, newest first.
+pub async fn fetch_albums(client: &SubsonicClient) -> Result<Vec<AlbumID3WithSongs>> {
+    let mut albums = Vec::new();
+
+    for item in list_albums(client).await? {
+        albums.push(client.album(&item.id).await?);
+    }
+
+    Ok(albums)
+}
+
+// Whether the album list still describes `album` the way it was stored.
+fn unchanged(album: &AlbumID3, item: &AlbumID3) -> bool {
+    album.name == item.name
+        && album.artist == item.artist
+        && album.year == item.year
+        && album.cover_art == item.cover_art
+        && album.song_count == item.song_count
+        && album.duration == item.duration
+}
+
+/// Brings the snapshot up to date, returning the number of albums fetched.
+///
+/// Unless `full` is set, nothing is fetched while the server's indexes are unchanged,
+/// and otherwise only albums that are new or whose name, artist, year, cover art, song
+/// count or duration changed. Edits to songs that leave all of those as they were, such
+/// as a corrected title, and albums deleted from the server wait for the next full
+/// refresh.
+pub async fn refresh(
+    client: &SubsonicClient,
+    snapshot: &mut Snapshot,
+    full: bool,
+) -> Result<usize> {
+    let since = if full {
+        None
+    } else {
+        snapshot.indexes_modified()?
+    };
+
+    let indexes = client.indexes(None, since).await?;
+
+    let unchanged_indexes = since.is_some()
+        && indexes.index.as_ref().is_none_or(Vec::is_empty)
+        && indexes.child.as_ref().is_none_or(Vec::is_empty);
+
+    if unchanged_indexes {
+        return Ok(0);
+    }
+
+    let albums = if full {
+        fetch_albums(client).await?
+    } else {
+        let stored = snapshot.albums_by_id()?;
+        let mut albums = Vec::new();
+
+        for item in list_albums(client).await? {
+            if !stored
+                .get(&item.id)
+                .is_some_and(|album| unchanged(album, &item))
+            {
+                albums.push(client.album(&item.id).await?);
+            }
+        }
+
+        albums
+    };
+
+    snapshot.store(&albums, full, Some(indexes.last_modified))?;
+
+    Ok(albums.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn album(id: &str, name: &str, songs: &[(&str, &str)]) -> Result<AlbumID3WithSongs> {
+        let songs: Vec<_> = songs
+            .iter()
+            .enumerate()
+            .map(|(track, (song_id, title))| {
+                json!({
+                    "id": song_id,
+                    "isDir": false,
+                    "title": title,
+                    "album": name,
+                    "albumId": id,
+                    "artist": "Miles Davis",
+                    "artistId": "ar-1",
+                    "track": track + 1
+                })
+            })
+            .collect();
+
+        Ok(serde_json::from_value(json!({
+            "id": id,
+            "name": name,
+            "artist": "Miles Davis",
+            "artistId": "ar-1",
+            "created": "2024-03-01T12:00:00Z",
+            "duration": 2600,
+            "songCount": songs.len(),
+            "song": songs
+        }))?)
+    }
+
+    fn titles(songs: &[Song]) -> Vec<&str> {
+        songs.iter().map(|song| song.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_store_and_browse() -> Result<()> {
+        let mut snapshot = Snapshot::open_in_memory()?;
+
+        snapshot.store(
+            &[album(
+                "al-1",
+                "Kind of Blue",
+                &[("s-2", "Freddie Freeloader"), ("s-1", "So What")],
+            )?],
+            false,
+            None,
+        )?;
+
+        assert_eq!(
+            snapshot.all_artists()?,
+            vec![SnapshotArtist {
+                id: ArtistId::unchecked("ar-1"),
+                name: "Miles Davis".to_owned(),
+                album_count: 1,
+            }]
+        );
+        assert_eq!(
+            snapshot.artist_albums(&ArtistId::unchecked("ar-1"))?[0].name,
+            "Kind of Blue"
+        );
+        assert_eq!(
+            titles(&snapshot.album_songs(&AlbumId::unchecked("al-1"))?),
+            vec!["Freddie Freeloader", "So What"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replacing_keeps_local_files() -> Result<()> {
+        let mut snapshot = Snapshot::open_in_memory()?;
+
+        snapshot.store(
+            &[album("al-1", "Kind of Blue", &[("s-1", "So What")])?],
+            false,
+            None,
+        )?;
+        snapshot.set_local_file(&SongId::unchecked("s-1"), Path::new("/music/so-what.flac"))?;
+
+        snapshot.store(
+            &[album("al-1", "Kind of Blue", &[("s-1", "So What")])?],
+            true,
+            None,
+        )?;
+
+        assert_eq!(titles(&snapshot.local_songs()?), vec!["So What"]);
+        assert_eq!(
+            snapshot.local_files()?.get(&SongId::unchecked("s-1")),
+            Some(&PathBuf::from("/music/so-what.flac"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_removing_local_files() -> Result<()> {
+        let mut snapshot = Snapshot::open_in_memory()?;
+
+        snapshot.store(
+            &[album(
+                "al-1",
+                "Kind of Blue",
+                &[("s-1", "So What"), ("s-2", "Freddie Freeloader")],
+            )?],
+            false,
+            None,
+        )?;
+        snapshot.set_local_file(&SongId::unchecked("s-1"), Path::new("/music/so-what.flac"))?;
+        snapshot.set_local_file(&SongId::unchecked("s-2"), Path::new("/music/freddie.flac"))?;
+
+        snapshot.remove_local_file(Path::new("/music/so-what.flac"))?;
+        snapshot.remove_local_file(Path::new("/music/never-saved.flac"))?;
+
+        assert_eq!(titles(&snapshot.local_songs()?), vec!["Freddie Freeloader"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refreshing_an_album_drops_removed_songs() -> Result<()> {
+        let mut snapshot = Snapshot::open_in_memory()?;
+
+        snapshot.store(
+            &[album(
+                "al-1",
+                "Kind of Blue",
+                &[("s-1", "So What"), ("s-2", "Flamenco Sketches")],
+            )?],
+            false,
+            None,
+        )?;
+        snapshot.store(
+            &[album("al-1", "Kind of Blue", &[("s-1", "So What")])?],
+            false,
+            None,
+        )?;
+
+        assert_eq!(
+            titles(&snapshot.album_songs(&AlbumId::unchecked("al-1"))?),
+            vec!["So What"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::history::Play;
+
+/// How listening time is totalled, by UTC calendar dates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    fn label(self, play: &Play) -> String {
+        let format = match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%G-W%V",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y",
+        };
+
+        play.played_at.format(format).to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub name: String,
+    pub plays: u64,
+    /// Plays of songs stopped early, see [`Play::stopped_early`].
+    pub stopped_early: u64,
+    /// Time listened, in seconds.
+    pub seconds: u64,
+}
+
+impl Tally {
+    fn add(&mut self, play: &Play) {
+        self.plays += 1;
+        self.stopped_early += u64::from(play.stopped_early);
+        self.seconds += play.seconds;
+    }
+
+    pub fn stopped_early_rate(&self) -> f64 {
+        match self.plays {
+            0 => 0.0,
+            plays => self.stopped_early as f64 / plays as f64,
+        }
+    }
+}
+
+fn tallies<'a>(plays: &'a [Play], names: impl Fn(&'a Play) -> Vec<String>) -> Vec<Tally> {
+    let mut tallies: BTreeMap<String, Tally> = BTreeMap::new();
+
+    for play in plays {
+        for name in names(play) {
+            tallies
+                .entry(name.clone())
+                .or_insert_with(|| Tally {
+                    name,
+                    ..Tally::default()
+                })
+                .add(play);
+        }
+    }
+
+    tallies.into_values().collect()
+}
+
+// Plays stopped early do not make an artist a favourite.
+fn top(mut tallies: Vec<Tally>, limit: usize) -> Vec<Tally> {
+    tallies.sort_by_key(|tally| {
+        (
+            Reverse(tally.plays - tally.stopped_early),
+            Reverse(tally.seconds),
+        )
+    });
+    tallies.truncate(limit);
+    tallies
+}
+
+fn unknown(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or("Unknown")
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub total: Tally,
+    pub stopped_early_rate: f64,
+    pub top_artists: Vec<Tally>,
+    pub top_albums: Vec<Tally>,
+    pub top_genres: Vec<Tally>,
+    /// Listening per period, oldest first.
+    pub periods: Vec<Tally>,
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+    section: &'a str,
+    name: &'a str,
+    plays: u64,
+    stopped_early: u64,
+    seconds: u64,
+    stopped_early_rate: f64,
+}
+
+impl Report {
+    /// Sums up `plays`, listing at most `limit` top artists, albums and genres.
+    pub fn new(plays: &[Play], period: Period, limit: usize) -> Self {
+        let mut total = Tally {
+            name: "all".to_owned(),
+            ..Tally::default()
+        };
+
+        for play in plays {
+            total.add(play);
+        }
+
+        Self {
+            stopped_early_rate: total.stopped_early_rate(),
+            total,
+            top_artists: top(
+                tallies(plays, |play| vec![unknown(&play.artist).to_owned()]),
+                limit,
+            ),
+            // Albums of the same name by different artists are told apart.
+            top_albums: top(
+                tallies(plays, |play| {
+                    vec![format!(
+                        "{} - {}",
+                        unknown(&play.artist),
+                        unknown(&play.album)
+                    )]
+                }),
+                limit,
+            ),
+            top_genres: top(tallies(plays, |play| play.genres.clone()), limit),
+            periods: tallies(plays, |play| vec![period.label(play)]),
+        }
+    }
+
+    pub fn write(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            ReportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+
+                let sections = [
+                    ("total", std::slice::from_ref(&self.total)),
+                    ("artist", &self.top_artists),
+                    ("album", &self.top_albums),
+                    ("genre", &self.top_genres),
+                    ("period", &self.periods),
+                ];
+
+                for (section, tallies) in sections {
+                    for tally in tallies {
+                        writer.serialize(Row {
+                            section,
+                            name: &tally.name,
+                            plays: tally.plays,
+                            stopped_early: tally.stopped_early,
+                            seconds: tally.seconds,
+                            stopped_early_rate: tally.stopped_early_rate(),
+                        })?;
+                    }
+                }
+
+                Ok(String::from_utf8(writer.into_inner()?)?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use crate::types::SongId;
+
+    use super::*;
+
+    fn play(artist: &str, album: &str, genres: &[&str], played_at: &str, seconds: u64) -> Play {
+        Play {
+            song_id: SongId::unchecked("s-1"),
+            title: "Song".to_owned(),
+            artist: Some(artist.to_owned()),
+            album: Some(album.to_owned()),
+            genres: genres.iter().map(|genre| genre.to_string()).collect(),
+            played_at: played_at.parse::<DateTime<Utc>>().unwrap_or_default(),
+            seconds,
+            stopped_early: seconds < 60,
+        }
+    }
+
+    fn plays() -> Vec<Play> {
+        vec![
+            play(
+                "Nina Simone",
+                "Pastel Blues",
+                &["Jazz"],
+                "2024-01-31T23:00:00Z",
+                300,
+            ),
+            play(
+                "Nina Simone",
+                "Pastel Blues",
+                &["Jazz"],
+                "2024-02-01T10:00:00Z",
+                10,
+            ),
+            play(
+                "Miles Davis",
+                "Kind of Blue",
+                &["Jazz", "Modal"],
+                "2024-02-02T10:00:00Z",
+                500,
+            ),
+            play(
+                "Miles Davis",
+                "Kind of Blue",
+                &["Jazz", "Modal"],
+                "2024-02-03T10:00:00Z",
+                400,
+            ),
+            play(
+                "Bill Evans",
+                "Greatest Hits",
+                &[],
+                "2024-03-01T10:00:00Z",
+                20,
+            ),
+        ]
+    }
+
+    fn names(tallies: &[Tally]) -> Vec<&str> {
+        tallies.iter().map(|tally| tally.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Report::new(&plays(), Period::Month, 2);
+
+        assert_eq!(
+            report.total,
+            Tally {
+                name: "all".to_owned(),
+                plays: 5,
+                stopped_early: 2,
+                seconds: 1230,
+            }
+        );
+        assert_eq!(report.stopped_early_rate, 0.4);
+        assert_eq!(
+            names(&report.top_artists),
+            vec!["Miles Davis", "Nina Simone"]
+        );
+        assert_eq!(
+            names(&report.top_albums),
+            vec!["Miles Davis - Kind of Blue", "Nina Simone - Pastel Blues"]
+        );
+        assert_eq!(names(&report.top_genres), vec!["Jazz", "Modal"]);
+        assert_eq!(
+            report
+                .periods
+                .iter()
+                .map(|tally| (tally.name.as_str(), tally.seconds))
+                .collect::<Vec<_>>(),
+            vec![("2024-01", 300), ("2024-02", 910), ("2024-03", 20)]
+        );
+        assert_eq!(
+            names(&Report::new(&plays(), Period::Week, 0).periods),
+            vec!["2024-W05", "2024-W09"]
+        );
+    }
+
+    #[test]
+    fn test_write_csv() -> Result<()> {
+        let report = Report::new(&plays()[..2], Period::Year, 10);
+
+        assert_eq!(
+            report.write(ReportFormat::Csv)?,
+            "section,name,plays,stopped_early,seconds,stopped_early_rate\n\
+             total,all,2,1,310,0.5\n\
+             artist,Nina Simone,2,1,310,0.5\n\
+             album,Nina Simone - Pastel Blues,2,1,310,0.5\n\
+             genre,Jazz,2,1,310,0.5\n\
+             period,2024,2,1,310,0.5\n"
+        );
+
+        Ok(())
+    }
+}
@@ -1,11 +1,25 @@
 use std::{
-    io::{Read, Seek, SeekFrom},
+    io::{ErrorKind, Read, Seek, SeekFrom},
     pin::Pin,
+    sync::mpsc::Receiver,
 };
 
 use futures::{AsyncRead, AsyncReadExt, StreamExt, TryStreamExt};
+use reqwest::{header::RANGE, StatusCode};
 use tokio::runtime::Handle;
 
+use crate::icy::IcyReader;
+
+/// How much already played audio a live stream keeps, so decoders can seek back a little.
+const LIVE_WINDOW: usize = 256 * 1024;
+
+/// How far past the loaded data a read may start before the stream is reopened there,
+/// rather than downloading everything in between.
+const REOPEN_DISTANCE: usize = 512 * 1024;
+
+/// Opens the same stream again, starting at the given byte.
+type Reopen<R> = Box<dyn FnMut(usize) -> std::io::Result<R> + Send + Sync>;
+
 pub struct SyncReader {
     reader: Pin<Box<dyn AsyncRead + Send + Sync>>,
     handle: Handle,
@@ -29,7 +43,12 @@ impl Read for SyncReader {
 pub struct SongStream<R: Read> {
     stream: R,
     loaded: Vec<u8>,
+    // Position in the stream of `loaded[0]`, non-zero for live streams and reopened ones.
+    discarded: usize,
     index: usize,
+    len: Option<usize>,
+    window: Option<usize>,
+    reopen: Option<Reopen<R>>,
 }
 
 impl<R: Read> SongStream<R> {
@@ -40,12 +59,40 @@ impl<R: Read> SongStream<R> {
                 Some(expected) => Vec::with_capacity(expected),
                 None => Vec::new(),
             },
+            discarded: 0,
+            index: 0,
+            len: expected,
+            window: None,
+            reopen: None,
+        }
+    }
+
+    /// Lets reads far from the loaded data open the stream again at their position.
+    pub fn with_reopen(
+        mut self,
+        reopen: impl FnMut(usize) -> std::io::Result<R> + Send + Sync + 'static,
+    ) -> SongStream<R> {
+        self.reopen = Some(Box::new(reopen));
+        self
+    }
+
+    /// A stream without an end, such as internet radio, that only keeps the most
+    /// recently read `window` bytes around.
+    pub fn live(stream: R, window: usize) -> SongStream<R> {
+        SongStream {
+            stream,
+            loaded: Vec::new(),
+            discarded: 0,
             index: 0,
+            len: None,
+            window: Some(window),
+            reopen: None,
         }
     }
 
     fn ensure(&mut self, pos: usize) -> std::io::Result<()> {
         let current = self.loaded.len();
+        let pos = pos - self.discarded;
 
         if pos <= current {
             return Ok(());
@@ -73,23 +120,69 @@ impl<R: Read> SongStream<R> {
 
         Ok(())
     }
+
+    fn reopen_at_index(&mut self) -> std::io::Result<()> {
+        let behind = self.index < self.discarded;
+        let far_ahead = self.index > self.discarded + self.loaded.len() + REOPEN_DISTANCE;
+
+        let Some(reopen) = self.reopen.as_mut().filter(|_| behind || far_ahead) else {
+            return Ok(());
+        };
+
+        match reopen(self.index) {
+            Ok(stream) => {
+                self.stream = stream;
+                self.loaded.clear();
+                self.discarded = self.index;
+
+                Ok(())
+            }
+            // Without range requests the data up to the position is read instead.
+            Err(_) if far_ahead => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn discard_played(&mut self) {
+        let Some(window) = self.window else {
+            return;
+        };
+
+        let played = self.index - self.discarded;
+
+        if played > window * 2 {
+            let discard = played - window;
+
+            self.loaded.drain(..discard);
+            self.discarded += discard;
+        }
+    }
 }
 
 impl<R: Read> Read for SongStream<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let bytes_requested = buf.len();
 
+        if self.len.is_some_and(|len| self.index >= len) {
+            return Ok(0);
+        }
+
+        self.reopen_at_index()?;
         self.ensure(self.index + bytes_requested)?;
 
-        let bytes_available = self.loaded.len() - self.index;
+        // Reads past the end, after seeking beyond it, find nothing.
+        let index = (self.index - self.discarded).min(self.loaded.len());
+        let bytes_available = self.loaded.len() - index;
 
         let to_write = std::cmp::min(bytes_requested, bytes_available);
 
-        let loaded_end = self.index + to_write;
+        let loaded_end = index + to_write;
+
+        buf[..to_write].copy_from_slice(&self.loaded[index..loaded_end]);
 
-        buf[..to_write].copy_from_slice(&self.loaded[self.index..loaded_end]);
+        self.index += to_write;
 
-        self.index = loaded_end;
+        self.discard_played();
 
         Ok(to_write)
     }
@@ -98,14 +191,60 @@ impl<R: Read> Read for SongStream<R> {
 impl<R: Read> Seek for SongStream<R> {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         use SeekFrom::*;
-        match pos {
-            Start(pos) => self.index = pos as usize,
-            End(pos) => self.index = ((self.loaded.len() as i64) + pos) as usize,
-            Current(pos) => self.index = ((self.index as i64) + pos) as usize,
+
+        let end = self.len.unwrap_or(self.discarded + self.loaded.len());
+
+        let target = match pos {
+            Start(pos) => Some(pos as i64),
+            End(_) if self.window.is_some() => None,
+            End(pos) => Some(end as i64 + pos),
+            Current(pos) => Some(self.index as i64 + pos),
+        };
+
+        match target {
+            Some(target)
+                if target >= self.discarded as i64 || (target >= 0 && self.reopen.is_some()) =>
+            {
+                self.index = target as usize;
+
+                Ok(target as u64)
+            }
+            _ => Err(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "Cannot seek to that position of a live stream.",
+            )),
         }
+    }
+}
 
-        Ok(0)
+fn sync_reader(response: reqwest::Response, handle: Handle) -> SyncReader {
+    let s = response
+        .bytes_stream()
+        .fuse()
+        .map_err(std::io::Error::other)
+        .into_async_read();
+
+    SyncReader::new(s, handle)
+}
+
+/// Requests `url` from byte `offset` on, for servers that honour range requests.
+async fn request_from(url: reqwest::Url, offset: usize) -> std::io::Result<reqwest::Response> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header(RANGE, format!("bytes={offset}-"))
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| std::io::Error::other(e.without_url()))?;
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(std::io::Error::new(
+            ErrorKind::Unsupported,
+            "The server does not support range requests.",
+        ));
     }
+
+    Ok(response)
 }
 
 pub fn from_response(response: reqwest::Response) -> SongStream<SyncReader> {
@@ -114,13 +253,147 @@ pub fn from_response(response: reqwest::Response) -> SongStream<SyncReader> {
         .get("Content-Length")
         .and_then(|v| v.to_str().ok().and_then(|v| v.parse().ok()));
 
-    let s = response
-        .bytes_stream()
-        .fuse()
-        .map_err(std::io::Error::other)
-        .into_async_read();
+    let url = response.url().clone();
+    let handle = Handle::current();
+    let stream = sync_reader(response, handle.clone());
 
-    let sync_reader = SyncReader::new(s, Handle::current());
+    SongStream::new(stream, expected).with_reopen(move |offset| {
+        let response = handle.block_on(request_from(url.clone(), offset))?;
 
-    SongStream::new(sync_reader, expected)
+        Ok(sync_reader(response, handle.clone()))
+    })
+}
+
+/// Streams an internet radio station, returning the stream titles announced in its
+/// ICY metadata alongside the audio.
+pub fn live_from_response(
+    response: reqwest::Response,
+) -> (SongStream<IcyReader<SyncReader>>, Receiver<String>) {
+    let metaint = response
+        .headers()
+        .get("icy-metaint")
+        .and_then(|v| v.to_str().ok().and_then(|v| v.parse().ok()));
+
+    let (reader, titles) = IcyReader::new(sync_reader(response, Handle::current()), metaint);
+
+    (SongStream::live(reader, LIVE_WINDOW), titles)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn numbers(len: usize) -> Vec<u8> {
+        (0..len).map(|n| n as u8).collect()
+    }
+
+    #[test]
+    fn test_song_stream_seeks_back() -> std::io::Result<()> {
+        let mut stream = SongStream::new(Cursor::new(numbers(100)), None);
+
+        let mut buf = [0; 10];
+
+        stream.read_exact(&mut buf)?;
+        assert_eq!(stream.seek(SeekFrom::Start(2))?, 2);
+        stream.read_exact(&mut buf[..2])?;
+
+        assert_eq!(&buf[..2], &[2, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_song_stream_reads_nothing_past_the_end() -> std::io::Result<()> {
+        let mut stream = SongStream::new(Cursor::new(numbers(100)), None);
+
+        let mut buf = [0; 10];
+
+        assert_eq!(stream.seek(SeekFrom::Start(200))?, 200);
+        assert_eq!(stream.read(&mut buf)?, 0);
+
+        assert_eq!(stream.seek(SeekFrom::End(-2))?, 98);
+        assert_eq!(stream.read(&mut buf)?, 2);
+        assert_eq!(&buf[..2], &[98, 99]);
+
+        Ok(())
+    }
+
+    fn reopening(data: Vec<u8>) -> SongStream<Cursor<Vec<u8>>> {
+        let len = data.len();
+
+        SongStream::new(Cursor::new(data.clone()), Some(len)).with_reopen(move |offset| {
+            let mut reopened = Cursor::new(data.clone());
+            reopened.set_position(offset as u64);
+            Ok(reopened)
+        })
+    }
+
+    #[test]
+    fn test_song_stream_reopens_far_ahead_and_behind() -> std::io::Result<()> {
+        let mut stream = reopening(numbers(4 * REOPEN_DISTANCE));
+
+        let mut buf = [0; 10];
+
+        assert_eq!(stream.seek(SeekFrom::End(0))?, 4 * REOPEN_DISTANCE as u64);
+        assert_eq!(stream.read(&mut buf)?, 0);
+
+        stream.seek(SeekFrom::Start(3 * REOPEN_DISTANCE as u64 + 5))?;
+        stream.read_exact(&mut buf[..1])?;
+
+        assert_eq!(buf[0], 5);
+        assert!(stream.loaded.len() < REOPEN_DISTANCE);
+
+        stream.seek(SeekFrom::Start(2))?;
+        stream.read_exact(&mut buf[..2])?;
+
+        assert_eq!(&buf[..2], &[2, 3]);
+        assert_eq!(stream.discarded, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_song_stream_reads_through_without_range_requests() -> std::io::Result<()> {
+        let data = numbers(2 * REOPEN_DISTANCE);
+        let mut stream = SongStream::new(Cursor::new(data), None)
+            .with_reopen(|_| Err(std::io::Error::from(ErrorKind::Unsupported)));
+
+        let mut buf = [0; 1];
+
+        stream.seek(SeekFrom::Start(2 * REOPEN_DISTANCE as u64 - 1))?;
+        stream.read_exact(&mut buf)?;
+
+        assert_eq!(buf[0], 255);
+        assert_eq!(stream.loaded.len(), 2 * REOPEN_DISTANCE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_live_stream_drops_played_data() -> std::io::Result<()> {
+        let mut stream = SongStream::live(Cursor::new(numbers(1000)), 10);
+
+        let mut buf = [0; 50];
+
+        stream.read_exact(&mut buf)?;
+
+        assert!(stream.loaded.len() <= 20);
+        assert_eq!(stream.seek(SeekFrom::Current(-10))?, 40);
+
+        stream.read_exact(&mut buf[..1])?;
+        assert_eq!(buf[0], 40);
+
+        assert_eq!(
+            stream.seek(SeekFrom::Start(0)).map_err(|e| e.kind()),
+            Err(ErrorKind::Unsupported)
+        );
+        assert_eq!(
+            stream.seek(SeekFrom::End(0)).map_err(|e| e.kind()),
+            Err(ErrorKind::Unsupported)
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::api_types::Song;
+use crate::client::{AlbumListType, SubsonicClient};
+use crate::download::{download_song, FileNameTemplate, Outcome};
+use crate::types::{PlaylistId, SongId, Strong};
+
+pub const MANIFEST_FILE_NAME: &str = ".knuckles-sync.json";
+
+/// What to keep in a synced directory.
+pub enum Selector {
+    Playlist(PlaylistId),
+    /// Starred songs and the songs of starred albums.
+    Starred,
+    AlbumList {
+        list_type: AlbumListType,
+        size: u64,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub id: SongId,
+    pub size: Option<u64>,
+}
+
+/// The files a sync has written, so that later syncs only ever delete those.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Paths relative to the synced directory.
+    pub files: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+impl Manifest {
+    pub async fn load(directory: &Path) -> Result<Self> {
+        let path = directory.join(MANIFEST_FILE_NAME);
+
+        match fs::read_to_string(&path).await {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Could not read sync manifest {}.", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the manifest in one step, so an interrupted sync leaves the old one intact.
+    pub async fn save(&self, directory: &Path) -> Result<()> {
+        let path = directory.join(MANIFEST_FILE_NAME);
+        let partial = path.with_extension("json.partial");
+
+        fs::write(&partial, serde_json::to_vec_pretty(self)?).await?;
+        fs::rename(&partial, &path).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Plan {
+    /// Every wanted song, complete files are skipped when downloading.
+    pub download: Vec<(PathBuf, Song)>,
+    /// Synced files that are no longer wanted, or now hold a different song.
+    pub delete: Vec<PathBuf>,
+}
+
+pub fn plan(manifest: &Manifest, template: &FileNameTemplate, songs: Vec<Song>) -> Plan {
+    let mut seen = HashSet::new();
+    let mut planned = HashSet::new();
+    let mut download = Vec::new();
+
+    for song in songs {
+        if song.is_dir || !seen.insert(song.id.get_ref().clone()) {
+            continue;
+        }
+
+        let path = template.path_for(&song);
+
+        // Two songs with the same name, the first one wins.
+        if planned.insert(path.clone()) {
+            download.push((path, song));
+        }
+    }
+
+    let wanted: HashMap<&Path, &SongId> = download
+        .iter()
+        .map(|(path, song)| (path.as_path(), &song.id))
+        .collect();
+
+    let delete = manifest
+        .files
+        .iter()
+        .filter(|(path, entry)| wanted.get(path.as_path()) != Some(&&entry.id))
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    Plan { download, delete }
+}
+
+async fn selected_songs(client: &SubsonicClient, selector: &Selector) -> Result<Vec<Song>> {
+    match selector {
+        Selector::Playlist(id) => Ok(client.playlist(id).await?.entry.unwrap_or_default()),
+        Selector::Starred => {
+            let starred = client.starred().await?;
+
+            let mut songs = starred.song.unwrap_or_default();
+
+            for album in starred.album.unwrap_or_default() {
+                songs.extend(client.album(&album.id).await?.song);
+            }
+
+            Ok(songs)
+        }
+        Selector::AlbumList { list_type, size } => {
+            let albums = client
+                .albums2(list_type.clone(), Some(*size), None, None)
+                .await?;
+
+            let mut songs = Vec::new();
+
+            for album in albums {
+                songs.extend(client.album(&album.id).await?.song);
+            }
+
+            Ok(songs)
+        }
+    }
+}
+
+pub async fn wanted_songs(client: &SubsonicClient, selectors: &[Selector]) -> Result<Vec<Song>> {
+    let mut songs = Vec::new();
+
+    for selector in selectors {
+        songs.extend(selected_songs(client, selector).await?);
+    }
+
+    Ok(songs)
+}
+
+// Manifests are plain files, so never follow one out of the synced directory.
+fn is_inside(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+async fn remove_synced_file(directory: &Path, path: &Path) -> Result<()> {
+    let full_path = directory.join(path);
+
+    match fs::remove_file(&full_path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    // Clear out directories left empty, stopping at the first one still in use.
+    for parent in full_path.ancestors().skip(1) {
+        if parent == directory || fs::remove_dir(parent).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+pub enum Progress<'a> {
+    Deleted(&'a Path),
+    Synced(&'a Path, &'a Song, Outcome),
+    Failed(&'a Path, &'a anyhow::Error),
+}
+
+/// Makes `directory` hold exactly the selected songs, saving the manifest after every
+/// change so that an interrupted sync can simply be run again.
+pub async fn sync(
+    client: &SubsonicClient,
+    directory: &Path,
+    selectors: &[Selector],
+    template: &FileNameTemplate,
+    mut progress: impl FnMut(Progress),
+) -> Result<()> {
+    fs::create_dir_all(directory).await?;
+
+    let mut manifest = Manifest::load(directory).await?;
+    let plan = plan(&manifest, template, wanted_songs(client, selectors).await?);
+
+    for path in &plan.delete {
+        if is_inside(path) {
+            remove_synced_file(directory, path)
+                .await
+                .with_context(|| format!("Could not delete {}.", path.display()))?;
+
+            progress(Progress::Deleted(path));
+        }
+
+        manifest.files.remove(path);
+        manifest.save(directory).await?;
+    }
+
+    let mut failed = 0;
+
+    for (path, song) in &plan.download {
+        match download_song(client, song, &directory.join(path)).await {
+            // Files that were already there belong to the user, unless a sync wrote them.
+            Ok(Outcome::Skipped) if !manifest.files.contains_key(path) => {
+                progress(Progress::Synced(path, song, Outcome::Skipped));
+            }
+            Ok(outcome) => {
+                progress(Progress::Synced(path, song, outcome));
+
+                let entry = ManifestEntry {
+                    id: song.id.clone(),
+                    size: song.size,
+                };
+
+                if manifest.files.get(path) != Some(&entry) {
+                    manifest.files.insert(path.clone(), entry);
+                    manifest.save(directory).await?;
+                }
+            }
+            Err(e) => {
+                progress(Progress::Failed(path, &e));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("Could not sync {failed} songs, run the sync again to retry them.");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn song(id: &str, title: &str) -> Result<Song> {
+        Ok(serde_json::from_value(json!({
+            "id": id,
+            "isDir": false,
+            "title": title,
+            "artist": "Nina Simone",
+            "album": "Pastel Blues",
+            "year": 1965,
+            "track": 1,
+            "suffix": "mp3",
+            "size": 100
+        }))?)
+    }
+
+    fn entry(id: &str) -> ManifestEntry {
+        ManifestEntry {
+            id: SongId::unchecked(id),
+            size: Some(100),
+        }
+    }
+
+    fn ids(plan: &Plan) -> Vec<&str> {
+        plan.download
+            .iter()
+            .map(|(_, song)| song.id.get_ref().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_skips_duplicate_songs() -> Result<()> {
+        let template = FileNameTemplate::new("{title}")?;
+
+        let songs = vec![
+            song("a", "Sinnerman")?,
+            song("a", "Sinnerman")?,
+            song("b", "Sinnerman")?,
+            song("c", "Trouble in Mind")?,
+        ];
+
+        let plan = plan(&Manifest::default(), &template, songs);
+
+        assert_eq!(ids(&plan), vec!["a", "c"]);
+        assert!(plan.delete.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_deletes_stale_and_replaced_files() -> Result<()> {
+        let template = FileNameTemplate::new("{title}")?;
+
+        let mut manifest = Manifest::default();
+        manifest.files.insert("Sinnerman".into(), entry("a"));
+        manifest.files.insert("Be My Husband".into(), entry("b"));
+        manifest
+            .files
+            .insert("Trouble in Mind".into(), entry("old"));
+
+        let songs = vec![song("a", "Sinnerman")?, song("c", "Trouble in Mind")?];
+
+        let plan = plan(&manifest, &template, songs);
+
+        assert_eq!(ids(&plan), vec!["a", "c"]);
+        assert_eq!(
+            plan.delete,
+            vec![
+                PathBuf::from("Be My Husband"),
+                PathBuf::from("Trouble in Mind")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_paths_stay_inside() {
+        assert!(is_inside(Path::new(
+            "Nina Simone/1965 - Pastel Blues/1-01 Sinnerman.mp3"
+        )));
+        assert!(!is_inside(Path::new("../Sinnerman.mp3")));
+        assert!(!is_inside(Path::new("/tmp/Sinnerman.mp3")));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_round_trip() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+
+        assert_eq!(Manifest::load(directory.path()).await?, Manifest::default());
+
+        let template =
+            FileNameTemplate::new("{artist}/{year} - {album}/{disc}-{track} {title}.{suffix}")?;
+
+        let mut manifest = Manifest::default();
+        manifest
+            .files
+            .insert(template.path_for(&song("a", "Sinnerman")?), entry("a"));
+        manifest.save(directory.path()).await?;
+
+        assert_eq!(Manifest::load(directory.path()).await?, manifest);
+
+        Ok(())
+    }
+}
@@ -1,17 +1,133 @@
 use core::fmt;
 
-use serde::Deserialize;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 
+use crate::error::ValidationError;
 use crate::macros::strong_alias;
 pub use crate::strong::Strong;
 
-strong_alias!(ServerUrl, String, Debug, PartialEq, Eq);
-strong_alias!(Username, String, Debug, PartialEq, Eq);
-strong_alias!(Password, String, Debug, PartialEq, Eq);
-strong_alias!(PasswordHash, String, Debug, PartialEq, Eq);
-strong_alias!(Salt, String, Debug, PartialEq, Eq);
+fn check_server_url(url: String) -> Result<String, ValidationError> {
+    let mut parsed = Url::parse(&url)
+        .map_err(|e| ValidationError(format!("`{url}` is not a valid URL ({e}).")))?;
 
-strong_alias!(AlbumId, String, Debug, PartialEq, Eq);
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(ValidationError(format!(
+            "Unsupported URL scheme `{}` in `{url}`, expected http or https.",
+            parsed.scheme()
+        )));
+    }
+
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(ValidationError(format!("URL `{url}` has no host.")));
+    }
+
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(ValidationError(format!(
+            "URL `{url}` must not have a query or fragment."
+        )));
+    }
+
+    // Servers hosted under a sub-path are joined against, so the path must be a directory.
+    if !parsed.path().ends_with('/') {
+        let path = format!("{}/", parsed.path());
+        parsed.set_path(&path);
+    }
+
+    Ok(parsed.into())
+}
+
+fn check_username(username: String) -> Result<String, ValidationError> {
+    if username.trim().is_empty() {
+        return Err(ValidationError("Username must not be empty.".to_owned()));
+    }
+
+    Ok(username)
+}
+
+strong_alias!(
+    ServerUrl,
+    String,
+    check = check_server_url,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize
+);
+strong_alias!(
+    Username,
+    String,
+    check = check_username,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize
+);
+strong_alias!(Password, String, Debug, PartialEq, Eq, Serialize);
+strong_alias!(PasswordHash, String, Debug, PartialEq, Eq, Serialize);
+strong_alias!(Salt, String, Debug, PartialEq, Eq, Serialize);
+
+strong_alias!(AlbumId, String, Debug, PartialEq, Eq, Hash);
 strong_alias!(ArtistId, String, Debug, PartialEq, Eq);
+strong_alias!(DirectoryId, String, Debug, PartialEq, Eq);
 strong_alias!(MusicFolderId, String, Debug, PartialEq, Eq);
-strong_alias!(SongId, String, Debug, PartialEq, Eq);
+strong_alias!(PlaylistId, String, Debug, PartialEq, Eq);
+strong_alias!(PodcastChannelId, String, Debug, PartialEq, Eq);
+strong_alias!(PodcastEpisodeId, String, Debug, PartialEq, Eq);
+strong_alias!(RadioStationId, String, Debug, PartialEq, Eq);
+strong_alias!(ShareId, String, Debug, PartialEq, Eq);
+strong_alias!(SongId, String, Debug, Default, PartialEq, Eq, Hash, Serialize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_url() {
+        let url = |value: &str| ServerUrl::new(value).map(Strong::get);
+
+        assert_eq!(
+            url("https://subsonic.example.com"),
+            Ok("https://subsonic.example.com/".to_owned())
+        );
+        assert_eq!(
+            url("http://example.com:4533/music"),
+            Ok("http://example.com:4533/music/".to_owned())
+        );
+        assert_eq!(
+            url("https://example.com/music/"),
+            Ok("https://example.com/music/".to_owned())
+        );
+        assert_eq!(
+            url("dummyurl"),
+            Err(ValidationError(
+                "`dummyurl` is not a valid URL (relative URL without a base).".to_owned()
+            ))
+        );
+        assert_eq!(
+            url("ftp://example.com"),
+            Err(ValidationError(
+                "Unsupported URL scheme `ftp` in `ftp://example.com`, expected http or https."
+                    .to_owned()
+            ))
+        );
+        assert_eq!(
+            url("https://example.com/?u=admin"),
+            Err(ValidationError(
+                "URL `https://example.com/?u=admin` must not have a query or fragment.".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_username() {
+        assert_eq!(
+            Username::new("test").map(Strong::get),
+            Ok("test".to_owned())
+        );
+        assert_eq!(
+            Username::new("  "),
+            Err(ValidationError("Username must not be empty.".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,1046 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use knuckles::api_types::{AlbumID3WithSongs, Role, Song};
+use knuckles::client::{
+    AlbumListType, JukeboxAction, RandomSongsFilter, SubsonicClient, UserSettings,
+};
+use knuckles::download::{download_song, FileNameTemplate, Outcome};
+use knuckles::error::ArgumentError;
+use knuckles::lyrics::song_lyrics;
+use knuckles::playlist::{match_entries, MatchedBy, PlaylistFile, PlaylistFormat};
+use knuckles::radio::Radio;
+use knuckles::smart::{save_to_server, SmartPlaylist};
+use knuckles::snapshot::{refresh, Snapshot};
+use knuckles::sync::{sync, Manifest, ManifestEntry, Selector};
+use knuckles::token::TokenInfo;
+use knuckles::types::{
+    AlbumId, ArtistId, MusicFolderId, Password, PasswordHash, PodcastChannelId, RadioStationId,
+    Salt, ServerUrl, SongId, Username,
+};
+
+const AUTH_QUERY: &str = "f=json&u=user&t=a1b2c3&s=abcde&v=1.16.1&c=knuckles";
+
+fn client_for(server: &MockServer) -> SubsonicClient {
+    SubsonicClient {
+        url: ServerUrl::unchecked(server.uri()),
+        username: Username::unchecked("user"),
+        token_info: TokenInfo {
+            hash: PasswordHash::unchecked("a1b2c3"),
+            salt: Salt::unchecked("abcde"),
+        },
+    }
+}
+
+fn ok_response(body: serde_json::Value) -> ResponseTemplate {
+    let mut response = json!({
+        "status": "ok",
+        "version": "1.16.1",
+        "type": "navidrome",
+        "serverVersion": "0.51.1",
+        "openSubsonic": true,
+    });
+
+    if let (Some(response), Some(body)) = (response.as_object_mut(), body.as_object()) {
+        response.extend(body.clone());
+    }
+
+    ResponseTemplate::new(200).set_body_json(json!({ "subsonic-response": response }))
+}
+
+async fn received_queries(server: &MockServer) -> Vec<String> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|request| request.url.query().unwrap_or_default().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_albums_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbumList"))
+        .respond_with(ok_response(json!({
+            "albumList": {
+                "album": [{
+                    "id": "al-1",
+                    "created": "2024-03-01T00:00:00Z",
+                    "duration": 2400,
+                    "isDir": true,
+                    "songCount": 10,
+                    "title": "Goldberg Variations",
+                    "year": 1981
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let albums = client_for(&server)
+        .albums(
+            AlbumListType::ByYear {
+                from_year: 1980,
+                to_year: 1989,
+            },
+            Some(500),
+            Some(20),
+            Some(MusicFolderId::unchecked("3")),
+        )
+        .await?;
+
+    assert_eq!(albums.len(), 1);
+    assert_eq!(albums[0].title, "Goldberg Variations");
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&fromYear=1980&toYear=1989&type=byYear&size=500&offset=20&musicFolderId=3"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_albums_size_is_bounded() -> Result<()> {
+    let server = MockServer::start().await;
+
+    let error = client_for(&server)
+        .albums(AlbumListType::Newest, Some(501), None, None)
+        .await
+        .expect_err("size above the protocol maximum should be rejected");
+
+    assert_eq!(
+        error.downcast_ref::<ArgumentError>(),
+        Some(&ArgumentError::TooLarge {
+            parameter: "size",
+            value: 501,
+            max: 500,
+        })
+    );
+
+    assert!(received_queries(&server).await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_songs_by_genre_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getSongsByGenre"))
+        .respond_with(ok_response(json!({
+            "songsByGenre": {
+                "song": [{ "id": "s-1", "isDir": false, "title": "Aria", "genre": "Baroque" }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let songs = client_for(&server)
+        .songs_by_genre(
+            "Baroque & Early",
+            Some(50),
+            Some(100),
+            Some(MusicFolderId::unchecked("2")),
+        )
+        .await?;
+
+    assert_eq!(songs.len(), 1);
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&genre=Baroque+%26+Early&count=50&offset=100&musicFolderId=2"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_random_songs_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getRandomSongs"))
+        .respond_with(ok_response(json!({
+            "randomSongs": {
+                "song": [{ "id": "s-1", "isDir": false, "title": "So What", "genre": "Jazz" }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let filter = RandomSongsFilter {
+        genre: Some("Jazz".to_owned()),
+        from_year: Some(1950),
+        to_year: Some(1969),
+        music_folder_id: None,
+    };
+
+    let songs = client_for(&server).random_songs(Some(20), &filter).await?;
+
+    assert_eq!(songs.len(), 1);
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&size=20&genre=Jazz&fromYear=1950&toYear=1969"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_radio_falls_back_to_random_songs() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getSimilarSongs2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": "1.16.1",
+                "type": "subsonic",
+                "serverVersion": "6.1.6",
+                "openSubsonic": false,
+                "error": { "code": 0, "message": "Last.fm is not configured" }
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getRandomSongs"))
+        .respond_with(ok_response(json!({
+            "randomSongs": {
+                "song": [{ "id": "s-1", "isDir": false, "title": "So What" }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let radio = Radio::new(
+        Some(ArtistId::unchecked("ar-1")),
+        RandomSongsFilter::default(),
+    );
+    let songs = radio
+        .next_batch(&client_for(&server), None, &VecDeque::new())
+        .await;
+
+    assert_eq!(
+        songs
+            .iter()
+            .map(|song| song.title.as_str())
+            .collect::<Vec<_>>(),
+        vec!["So What"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_radio_batch_is_empty_when_random_songs_fail() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getRandomSongs"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let radio = Radio::new(None, RandomSongsFilter::default());
+
+    assert!(radio
+        .next_batch(&client_for(&server), None, &VecDeque::new())
+        .await
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_save_play_queue_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/savePlayQueue"))
+        .respond_with(ok_response(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ids = [SongId::unchecked("s-1"), SongId::unchecked("s-2")];
+
+    client_for(&server)
+        .save_play_queue(&ids, Some(&ids[1]), Some(61500))
+        .await?;
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&id=s-1&id=s-2&current=s-2&position=61500"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_play_queue_without_saved_queue() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getPlayQueue"))
+        .respond_with(ok_response(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert!(client_for(&server).play_queue().await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_bookmark_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/createBookmark"))
+        .respond_with(ok_response(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client_for(&server)
+        .create_bookmark(&SongId::unchecked("s-1"), 1234567, Some("Chapter 3"))
+        .await?;
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&id=s-1&position=1234567&comment=Chapter+3"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bookmarks() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getBookmarks"))
+        .respond_with(ok_response(json!({
+            "bookmarks": {
+                "bookmark": [{
+                    "position": 1234567,
+                    "username": "user",
+                    "created": "2024-03-01T12:00:00Z",
+                    "changed": "2024-03-02T12:00:00Z",
+                    "entry": { "id": "s-1", "isDir": false, "title": "Part One", "duration": 5400 }
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let bookmarks = client_for(&server).bookmarks().await?;
+
+    assert_eq!(bookmarks.len(), 1);
+    assert_eq!(bookmarks[0].entry.id, SongId::unchecked("s-1"));
+    assert_eq!(bookmarks[0].position, 1234567);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lyrics_fall_back_to_artist_and_title() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getLyricsBySongId"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": "1.16.1",
+                "type": "subsonic",
+                "serverVersion": "6.1.6",
+                "openSubsonic": false,
+                "error": { "code": 0, "message": "Not implemented" }
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getLyrics"))
+        .respond_with(ok_response(json!({
+            "lyrics": {
+                "artist": "Nina Simone",
+                "title": "Sinnerman",
+                "value": "Oh, sinnerman\nWhere you gonna run to?"
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let song: Song = serde_json::from_value(json!({
+        "id": "s-1",
+        "isDir": false,
+        "title": "Sinnerman",
+        "artist": "Nina Simone"
+    }))?;
+
+    let lyrics = song_lyrics(&client_for(&server), &song)
+        .await?
+        .expect("classic lyrics should be used");
+
+    assert!(!lyrics.synced);
+    assert_eq!(lyrics.lines.len(), 2);
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![
+            format!("{AUTH_QUERY}&id=s-1"),
+            format!("{AUTH_QUERY}&artist=Nina+Simone&title=Sinnerman"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_podcasts_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getPodcasts"))
+        .respond_with(ok_response(json!({
+            "podcasts": {
+                "channel": [{
+                    "id": "pc-1",
+                    "url": "https://podcast.example.com/feed.xml",
+                    "status": "downloading"
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let channels = client_for(&server)
+        .podcasts(Some(true), Some(&PodcastChannelId::unchecked("pc-1")))
+        .await?;
+
+    assert_eq!(channels.len(), 1);
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!("{AUTH_QUERY}&includeEpisodes=true&id=pc-1")]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_internet_radio_station_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/updateInternetRadioStation"))
+        .respond_with(ok_response(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client_for(&server)
+        .update_internet_radio_station(
+            &RadioStationId::unchecked("rs-1"),
+            "https://radio.example.com/live.mp3",
+            "Jazz & Blues",
+            None,
+        )
+        .await?;
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&id=rs-1&streamUrl=https%3A%2F%2Fradio.example.com%2Flive.mp3&name=Jazz+%26+Blues"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_share_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/createShare"))
+        .respond_with(ok_response(json!({
+            "shares": {
+                "share": [{
+                    "id": "sh-1",
+                    "url": "https://subsonic.example.com/share/abc",
+                    "username": "user",
+                    "created": "2024-03-01T12:00:00Z",
+                    "expires": "2024-04-01T00:00:00Z",
+                    "visitCount": 0,
+                    "description": "For Ana"
+                }]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let expires = DateTime::parse_from_rfc3339("2024-04-01T00:00:00Z")?.with_timezone(&Utc);
+
+    let share = client_for(&server)
+        .create_share(
+            &["al-1".to_owned(), "s-2".to_owned()],
+            Some("For Ana"),
+            Some(expires),
+        )
+        .await?;
+
+    assert_eq!(share.url, "https://subsonic.example.com/share/abc");
+    assert_eq!(share.expires, Some(expires));
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&id=al-1&id=s-2&description=For+Ana&expires=1711929600000"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_user_query() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/createUser"))
+        .respond_with(ok_response(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let settings = UserSettings {
+        email: Some("ana@example.com".to_owned()),
+        max_bit_rate: Some(320),
+        roles: vec![(Role::Stream, true), (Role::Admin, false)],
+        music_folder_ids: Some(vec![
+            MusicFolderId::unchecked("1"),
+            MusicFolderId::unchecked("2"),
+        ]),
+    };
+
+    client_for(&server)
+        .create_user("ana", &Password::unchecked("sesame"), &settings)
+        .await?;
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!(
+            "{AUTH_QUERY}&username=ana&password=enc%3A736573616d65&email=ana%40example.com&maxBitRate=320&streamRole=true&adminRole=false&musicFolderId=1&musicFolderId=2"
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_user_requires_email() -> Result<()> {
+    let server = MockServer::start().await;
+
+    match client_for(&server)
+        .create_user(
+            "ana",
+            &Password::unchecked("sesame"),
+            &UserSettings::default(),
+        )
+        .await
+    {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "Creating user ana requires an email address."
+        ),
+    }
+
+    assert!(received_queries(&server).await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_jukebox_get_returns_playlist() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/jukeboxControl"))
+        .respond_with(ok_response(json!({
+            "jukeboxPlaylist": {
+                "currentIndex": 1,
+                "playing": true,
+                "gain": 0.75,
+                "position": 42,
+                "entry": [
+                    { "id": "s-1", "isDir": false, "title": "Aria" },
+                    { "id": "s-2", "isDir": false, "title": "Variatio 1" }
+                ]
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = client_for(&server)
+        .jukebox_control(&JukeboxAction::Get)
+        .await?;
+
+    assert!(status.playing);
+    assert_eq!(status.current_index, 1);
+    assert_eq!(status.entry.map(|entry| entry.len()), Some(2));
+
+    assert_eq!(
+        received_queries(&server).await,
+        vec![format!("{AUTH_QUERY}&action=get")]
+    );
+
+    Ok(())
+}
+
+fn song_with_size(size: u64) -> Result<Song> {
+    Ok(serde_json::from_value(json!({
+        "id": "s-1",
+        "isDir": false,
+        "title": "Aria",
+        "size": size
+    }))?)
+}
+
+#[tokio::test]
+async fn test_download_skips_complete_files() -> Result<()> {
+    let server = MockServer::start().await;
+    let directory = tempfile::tempdir()?;
+    let path = directory.path().join("aria.flac");
+
+    std::fs::write(&path, b"complete")?;
+
+    let outcome = download_song(&client_for(&server), &song_with_size(8)?, &path).await?;
+
+    assert_eq!(outcome, Outcome::Skipped);
+    assert!(received_queries(&server).await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_download_resumes_partial_files() -> Result<()> {
+    let server = MockServer::start().await;
+    let directory = tempfile::tempdir()?;
+    let file = directory.path().join("Bach/aria.flac");
+
+    Mock::given(method("GET"))
+        .and(path("/rest/download"))
+        .and(header("Range", "bytes=4-"))
+        .respond_with(ResponseTemplate::new(206).set_body_bytes(b"lete".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    std::fs::create_dir_all(directory.path().join("Bach"))?;
+    std::fs::write(&file, b"comp")?;
+
+    let outcome = download_song(&client_for(&server), &song_with_size(8)?, &file).await?;
+
+    assert_eq!(outcome, Outcome::Resumed);
+    assert_eq!(std::fs::read(&file)?, b"complete");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_download_reports_subsonic_errors() -> Result<()> {
+    let server = MockServer::start().await;
+    let directory = tempfile::tempdir()?;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/download"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": "1.16.1",
+                "type": "navidrome",
+                "serverVersion": "0.51.1",
+                "openSubsonic": true,
+                "error": { "code": 70, "message": "Song not found" }
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let path = directory.path().join("aria.flac");
+
+    match download_song(&client_for(&server), &song_with_size(8)?, &path).await {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => assert_eq!(e.to_string(), "Subsonic error 70: Song not found"),
+    }
+
+    assert!(!path.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_replaces_stale_files() -> Result<()> {
+    let server = MockServer::start().await;
+    let directory = tempfile::tempdir()?;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getStarred2"))
+        .respond_with(ok_response(json!({
+            "starred2": {
+                "song": [
+                    { "id": "s-1", "isDir": false, "title": "Aria", "suffix": "flac", "size": 4 },
+                    { "id": "s-2", "isDir": false, "title": "Mine", "suffix": "mp3", "size": 4 }
+                ]
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/download"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"aria".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    std::fs::create_dir_all(directory.path().join("Old"))?;
+    std::fs::write(directory.path().join("Old/Gone.mp3"), b"gone")?;
+    std::fs::write(directory.path().join("Mine.mp3"), b"mine")?;
+
+    let mut manifest = Manifest::default();
+    manifest.files.insert(
+        "Old/Gone.mp3".into(),
+        ManifestEntry {
+            id: SongId::unchecked("s-0"),
+            size: Some(4),
+        },
+    );
+    manifest.save(directory.path()).await?;
+
+    let template = FileNameTemplate::new("{title}.{suffix}")?;
+    let client = client_for(&server);
+
+    // The second sync finds everything in place and downloads nothing, and the file that
+    // was already there is left to the user.
+    for _ in 0..2 {
+        sync(
+            &client,
+            directory.path(),
+            &[Selector::Starred],
+            &template,
+            |_| {},
+        )
+        .await?;
+    }
+
+    assert_eq!(std::fs::read(directory.path().join("Aria.flac"))?, b"aria");
+    assert!(!directory.path().join("Old").exists());
+    assert!(directory.path().join("Mine.mp3").exists());
+    assert_eq!(
+        Manifest::load(directory.path())
+            .await?
+            .files
+            .into_keys()
+            .collect::<Vec<_>>(),
+        vec![std::path::PathBuf::from("Aria.flac")]
+    );
+
+    Ok(())
+}
+
+fn album_response(id: &str, created: &str) -> ResponseTemplate {
+    ok_response(json!({
+        "album": {
+            "id": id,
+            "name": format!("Album {id}"),
+            "created": created,
+            "duration": 300,
+            "songCount": 1,
+            "song": [
+                { "id": format!("s-{id}"), "isDir": false, "title": "Song", "albumId": id }
+            ]
+        }
+    }))
+}
+
+#[tokio::test]
+async fn test_snapshot_refresh_fetches_new_and_changed_albums() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getIndexes"))
+        .respond_with(ok_response(json!({
+            "indexes": {
+                "lastModified": 1714000000000_i64,
+                "ignoredArticles": "The",
+                "index": [{ "name": "A", "artist": [{ "id": "d-1", "name": "Artist" }] }]
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getAlbumList2"))
+        .respond_with(ok_response(json!({
+            "albumList2": {
+                "album": [
+                    {
+                        "id": "new", "created": "2024-04-01T00:00:00Z", "duration": 300,
+                        "songCount": 1, "name": "Album new"
+                    },
+                    {
+                        "id": "known", "created": "2024-03-01T00:00:00Z", "duration": 300,
+                        "songCount": 1, "name": "Album known"
+                    },
+                    {
+                        "id": "edited", "created": "2024-02-01T00:00:00Z", "duration": 300,
+                        "songCount": 1, "name": "Album edited"
+                    }
+                ]
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    for (id, created, fetches) in [
+        ("new", "2024-04-01T00:00:00Z", 1),
+        ("known", "2024-03-01T00:00:00Z", 0),
+        ("edited", "2024-02-01T00:00:00Z", 1),
+    ] {
+        Mock::given(method("GET"))
+            .and(path("/rest/getAlbum"))
+            .and(query_param("id", id))
+            .respond_with(album_response(id, created))
+            .expect(fetches)
+            .mount(&server)
+            .await;
+    }
+
+    let mut snapshot = Snapshot::open_in_memory()?;
+    let known: AlbumID3WithSongs = serde_json::from_value(json!({
+        "id": "known",
+        "name": "Album known",
+        "created": "2024-03-01T00:00:00Z",
+        "duration": 300,
+        "songCount": 1,
+        "song": [{ "id": "s-known", "isDir": false, "title": "Song", "albumId": "known" }]
+    }))?;
+    // A song was added to this album since the last refresh.
+    let edited: AlbumID3WithSongs = serde_json::from_value(json!({
+        "id": "edited",
+        "name": "Album edited",
+        "created": "2024-02-01T00:00:00Z",
+        "duration": 0,
+        "songCount": 0,
+        "song": []
+    }))?;
+    snapshot.store(
+        &[known, edited],
+        false,
+        Some("2024-03-01T00:00:00Z".parse()?),
+    )?;
+
+    let fetched = refresh(&client_for(&server), &mut snapshot, false).await?;
+
+    assert_eq!(fetched, 2);
+    assert_eq!(snapshot.all_songs()?.len(), 3);
+    assert_eq!(
+        snapshot.album_songs(&AlbumId::unchecked("edited"))?.len(),
+        1
+    );
+    assert_eq!(
+        received_queries(&server).await[0],
+        format!("{AUTH_QUERY}&ifModifiedSince=1709251200000")
+    );
+    assert_eq!(
+        snapshot.indexes_modified()?,
+        Some("2024-04-24T23:06:40Z".parse()?)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_refresh_skips_unchanged_library() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getIndexes"))
+        .respond_with(ok_response(json!({
+            "indexes": { "lastModified": 1709251200000_i64, "ignoredArticles": "The" }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut snapshot = Snapshot::open_in_memory()?;
+    snapshot.store(&[], false, Some("2024-03-01T00:00:00Z".parse()?))?;
+
+    assert_eq!(
+        refresh(&client_for(&server), &mut snapshot, false).await?,
+        0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_matches_by_path_then_search() -> Result<()> {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/rest/search3"))
+        .respond_with(ok_response(json!({
+            "searchResult3": {
+                "song": [
+                    { "id": "s-9", "isDir": false, "title": "Pink Moon", "artist": "Nick Drake" }
+                ]
+            }
+        })))
+        .mount(&server)
+        .await;
+
+    let library: Vec<Song> = serde_json::from_value(json!([
+        { "id": "s-1", "isDir": false, "title": "Blue", "path": "Joni Mitchell/Blue/06 Blue.flac" }
+    ]))?;
+
+    let playlist = PlaylistFile::parse(
+        PlaylistFormat::M3u8,
+        "#EXTM3U\n\
+         /home/me/Music/Joni Mitchell/Blue/06 Blue.flac\n\
+         #EXTINF:122,Nick Drake - Pink Moon\n\
+         Pink Moon.mp3\n\
+         #EXTINF:-1,Nobody - Nothing\n\
+         nothing.mp3\n",
+    )?;
+
+    let matches = match_entries(&client_for(&server), &library, playlist.entries).await?;
+
+    assert_eq!(
+        matches.songs,
+        vec![
+            (SongId::unchecked("s-1"), MatchedBy::Path),
+            (SongId::unchecked("s-9"), MatchedBy::Search),
+        ]
+    );
+    assert_eq!(
+        matches
+            .unmatched
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec!["Nobody - Nothing"]
+    );
+    assert_eq!(
+        received_queries(&server).await,
+        vec![
+            format!(
+                "{AUTH_QUERY}&query=Nick+Drake+Pink+Moon&artistCount=0&albumCount=0&songCount=20"
+            ),
+            format!("{AUTH_QUERY}&query=Nobody+Nothing&artistCount=0&albumCount=0&songCount=20"),
+            format!("{AUTH_QUERY}&query=Nothing&artistCount=0&albumCount=0&songCount=20"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_save_smart_playlist_replaces_own_playlist() -> Result<()> {
+    let server = MockServer::start().await;
+
+    let playlist = |id: &str, owner: &str| {
+        json!({
+            "id": id,
+            "name": "Old Jazz",
+            "owner": owner,
+            "changed": "2024-01-01T00:00:00Z",
+            "created": "2024-01-01T00:00:00Z",
+            "duration": 0,
+            "songCount": 0
+        })
+    };
+
+    Mock::given(method("GET"))
+        .and(path("/rest/getPlaylists"))
+        .respond_with(ok_response(json!({
+            "playlists": { "playlist": [playlist("p-1", "other"), playlist("p-2", "user")] }
+        })))
+        .mount(&server)
+        .await;
+
+    for endpoint in ["/rest/createPlaylist", "/rest/updatePlaylist"] {
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .respond_with(ok_response(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let smart = SmartPlaylist {
+        rules: vec!["starred".parse()?, "genre = jazz".parse()?],
+        ..SmartPlaylist::default()
+    };
+    let songs = [SongId::unchecked("s-1"), SongId::unchecked("s-2")];
+
+    let id = save_to_server(&client_for(&server), "Old Jazz", &smart, &songs).await?;
+
+    assert_eq!(id.to_string(), "p-2");
+    assert_eq!(
+        received_queries(&server).await,
+        vec![
+            AUTH_QUERY.to_owned(),
+            format!("{AUTH_QUERY}&playlistId=p-2&songId=s-1&songId=s-2"),
+            format!(
+                "{AUTH_QUERY}&playlistId=p-2&comment=Smart+playlist%3A+starred%2C+genre+%3D+jazz"
+            ),
+        ]
+    );
+
+    Ok(())
+}